@@ -0,0 +1,52 @@
+//! renders the lamp tree with the software renderer and compares it with a committed golden image.
+//! run with `--update` to overwrite the golden.
+
+extern crate demo_triadica_space;
+
+use demo_triadica_space::container::container;
+
+use glam::Vec3;
+use triadica::raster::{RgbaImage, SoftwareRenderer};
+use triadica::viewer::{Camera, CONE_BACK_SCALE, LOOK_DISTANCE};
+use triadica::TriadicaError;
+
+const WIDTH: usize = 480;
+const HEIGHT: usize = 360;
+const GOLDEN_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/goldens/lamp-tree.png");
+const OUTPUT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../target/lamp-tree.png");
+
+pub fn main() -> Result<(), TriadicaError> {
+  let tree = container().and_then(|x| x.compile_to_tree())?;
+
+  let camera = Camera {
+    position: Vec3::new(0.0, 600.0, 2400.0),
+    forward: Vec3::new(0.0, 0.0, -1.0),
+    upward: Vec3::new(0.0, 1.0, 0.0),
+    rightward: Vec3::new(-1.0, 0.0, 0.0),
+    look_distance: LOOK_DISTANCE,
+    cone_back_scale: CONE_BACK_SCALE,
+    viewport_ratio: HEIGHT as f32 / WIDTH as f32,
+  };
+
+  let mut renderer = SoftwareRenderer::new(WIDTH, HEIGHT);
+  renderer.render_tree(&tree, &camera)?;
+  let image = renderer.to_image();
+
+  if std::env::args().any(|arg| arg == "--update") {
+    image.write_png(GOLDEN_PATH)?;
+    println!("updated {GOLDEN_PATH}");
+    return Ok(());
+  }
+
+  image.write_png(OUTPUT_PATH)?;
+  let golden = RgbaImage::read_png(GOLDEN_PATH)?;
+  let diff = image.diff_pixels(&golden, 2)?;
+  if diff > 0 {
+    Err(TriadicaError::Image(format!(
+      "{diff} pixels differ from {GOLDEN_PATH}, see {OUTPUT_PATH}"
+    )))
+  } else {
+    println!("matches {GOLDEN_PATH}");
+    Ok(())
+  }
+}
@@ -12,7 +12,7 @@ use container::container;
 pub fn main() -> Result<(), JsValue> {
   println!("status ready");
 
-  let tree = Rc::new(RefCell::new(container().compile_to_tree()?));
+  let tree = Rc::new(RefCell::new(container()?.compile_to_tree()?));
   println!("flatterned {}", (*tree.borrow_mut()).to_list().len());
 
   Ok(())
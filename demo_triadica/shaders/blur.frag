@@ -0,0 +1,18 @@
+precision mediump float;
+
+uniform sampler2D u_source;
+uniform vec2 u_resolution;
+uniform vec2 u_direction;
+
+varying vec2 v_uv;
+
+// 9-tap gaussian along one direction, run twice for a 2D blur
+void main() {
+  vec2 step = u_direction / u_resolution;
+  vec4 color = texture2D(u_source, v_uv) * 0.2270270270;
+  color += texture2D(u_source, v_uv + step * 1.3846153846) * 0.3162162162;
+  color += texture2D(u_source, v_uv - step * 1.3846153846) * 0.3162162162;
+  color += texture2D(u_source, v_uv + step * 3.2307692308) * 0.0702702703;
+  color += texture2D(u_source, v_uv - step * 3.2307692308) * 0.0702702703;
+  gl_FragColor = color;
+}
@@ -0,0 +1,13 @@
+precision mediump float;
+
+uniform sampler2D u_scene;
+uniform sampler2D u_blurred;
+uniform float u_strength;
+
+varying vec2 v_uv;
+
+void main() {
+  vec4 scene = texture2D(u_scene, v_uv);
+  vec4 blurred = texture2D(u_blurred, v_uv);
+  gl_FragColor = vec4(scene.rgb + blurred.rgb * u_strength, 1.0);
+}
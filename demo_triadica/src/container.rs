@@ -1,19 +1,42 @@
-use triadica::DrawMode;
 use triadica::VertexDataValue;
 use triadica::{group, object, PackedAttrs, TriadicaElement};
-use web_sys::console::log_1;
+use triadica::{DrawMode, ShaderLibrary, TriadicaError, UniformMap};
 
 use std::rc::Rc;
 
 use crate::shape::compute_lamp_tree_vertices;
 
-pub fn container() -> TriadicaElement {
-  let vert_shader = include_str!("../shaders/demo.vert");
-  let frag_shader = include_str!("../shaders/demo.frag");
+/// shader files of the demo, by name
+const DEMO_SHADERS: [(&str, &str); 2] = [
+  ("demo.vert", include_str!("../shaders/demo.vert")),
+  ("demo.frag", include_str!("../shaders/demo.frag")),
+];
+
+/// shader to link from source of a demo shader file, includes are expanded in vertex shader
+pub fn demo_shader(name: &str, source: &str) -> Result<String, TriadicaError> {
+  match name {
+    "demo.vert" => ShaderLibrary::default().expand(name, source),
+    "demo.frag" => Ok(source.to_owned()),
+    _ => Err(TriadicaError::Other(format!("unknown shader `{name}`"))),
+  }
+}
+
+/// shader linked at start, from the bundled file
+pub fn bundled_shader(name: &str) -> Result<String, TriadicaError> {
+  let (_, source) = DEMO_SHADERS
+    .iter()
+    .find(|(file, _)| *file == name)
+    .ok_or_else(|| TriadicaError::Other(format!("unknown shader `{name}`")))?;
+  demo_shader(name, source)
+}
+
+pub fn container() -> Result<TriadicaElement, TriadicaError> {
+  let vert_shader = bundled_shader("demo.vert")?;
+  let frag_shader = bundled_shader("demo.frag")?;
 
   let lamp_attrs = compute_lamp_tree_vertices();
 
-  group(vec![
+  Ok(group(vec![
     object(
       DrawMode::LineStrip,
       vert_shader.to_owned(),
@@ -26,7 +49,7 @@ pub fn container() -> TriadicaElement {
         PackedAttrs::Item(vec![VertexDataValue::Vec3([0., 100., 0.])]),
         PackedAttrs::Item(vec![VertexDataValue::Vec3([0., 0., 0.])]),
       ]),
-      Rc::new(Vec::new),
+      Rc::new(UniformMap::new),
     ),
     object(
       DrawMode::LineStrip,
@@ -34,7 +57,7 @@ pub fn container() -> TriadicaElement {
       frag_shader.to_owned(),
       vec![("a_position".to_owned(), 3)],
       lamp_attrs,
-      Rc::new(Vec::new),
+      Rc::new(UniformMap::new),
     ),
-  ])
+  ]))
 }
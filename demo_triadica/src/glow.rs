@@ -0,0 +1,50 @@
+//! glow over the lamp tree, by blurring the scene at half size and adding it back
+
+use std::rc::Rc;
+
+use glam::Vec2;
+use triadica::{RenderTargetSpec, Renderer, ScreenPass, TriadicaElementTree, TriadicaError, UniformMap, UniformValue, WebGlBackend};
+
+pub struct GlowPasses {
+  blur_x: ScreenPass,
+  blur_y: ScreenPass,
+  composite: ScreenPass,
+}
+
+impl GlowPasses {
+  /// declares render targets on the renderer
+  pub fn new(renderer: &mut Renderer<WebGlBackend>) -> Self {
+    renderer.set_render_target(RenderTargetSpec::new("scene", 1.0, true));
+    renderer.set_render_target(RenderTargetSpec::new("blur-x", 0.5, false));
+    renderer.set_render_target(RenderTargetSpec::new("blur-y", 0.5, false));
+
+    let blur_frag = include_str!("../shaders/blur.frag");
+    let blur = |source: &str, direction: Vec2| {
+      ScreenPass::new(
+        blur_frag,
+        vec![("u_source".to_owned(), source.to_owned())],
+        Rc::new(move || UniformMap::from([("u_direction".to_owned(), UniformValue::Vec2(direction))])),
+      )
+    };
+
+    Self {
+      blur_x: blur("scene", Vec2::X),
+      blur_y: blur("blur-x", Vec2::Y),
+      composite: ScreenPass::new(
+        include_str!("../shaders/glow.frag"),
+        vec![
+          ("u_scene".to_owned(), "scene".to_owned()),
+          ("u_blurred".to_owned(), "blur-y".to_owned()),
+        ],
+        Rc::new(|| UniformMap::from([("u_strength".to_owned(), UniformValue::Float(1.2))])),
+      ),
+    }
+  }
+
+  pub fn paint(&self, renderer: &mut Renderer<WebGlBackend>, tree: &TriadicaElementTree) -> Result<(), TriadicaError> {
+    renderer.paint_into(Some("scene"), tree)?;
+    renderer.run_screen_pass(&self.blur_x, Some("blur-x"))?;
+    renderer.run_screen_pass(&self.blur_y, Some("blur-y"))?;
+    renderer.run_screen_pass(&self.composite, None)
+  }
+}
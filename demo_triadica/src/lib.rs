@@ -1,18 +1,22 @@
 pub mod container;
+mod glow;
 mod shape;
 
+use glam::Vec3;
 use triadica::global_window;
 use triadica::viewer;
-use triadica::ShaderProgramCaches;
-use web_sys::console::log_1;
+use triadica::viewer::Camera;
+use triadica::{ContextWatcher, Renderer, ShaderProgramCaches, StereoOptions, Viewport, ViewportRect, WebGlBackend};
+use web_sys::console::{error_1, log_1, warn_1};
 use web_sys::Element;
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
 use wasm_bindgen::{prelude::*, JsCast};
 // use web_sys::console::{log_1, log_2};
 use container::container;
+use glow::GlowPasses;
 use web_sys::WebGl2RenderingContext;
 
 #[wasm_bindgen(js_name = initApp)]
@@ -29,20 +33,56 @@ pub fn init_app() -> Result<(), JsValue> {
     .dyn_into::<WebGl2RenderingContext>()?;
 
   triadica::context_setup(&context);
+  let context_watcher = ContextWatcher::new(&canvas)?;
 
   let program_caches = Rc::new(RefCell::new(ShaderProgramCaches::default()));
+  let mut renderer = Renderer::new(WebGlBackend::new(context, program_caches));
+  let glow = GlowPasses::new(&mut renderer);
 
   log_1(&"status ready".into());
 
-  let tree = Rc::new(RefCell::new(container().compile_to_tree()?));
+  let tree = Rc::new(RefCell::new(container()?.compile_to_tree()?));
   log_1(&"flatterned".into());
 
+  // whole tree from the side at a fixed place, inset at top right over the free-fly view
+  let overview = Viewport::new(
+    ViewportRect::new(0.72, 0.68, 0.26, 0.3),
+    Camera::looking_at(Vec3::new(0.0, 600.0, 1800.0), Vec3::new(0.0, 600.0, 0.0), Vec3::Y),
+  );
+
   let f = Rc::new(RefCell::new(None));
   let g = f.clone();
 
   *g.borrow_mut() = Some(Closure::wrap(Box::new(move || {
+    // uploads the tree again after the context is restored
+    if let Err(e) = context_watcher.apply(&mut renderer, &tree.borrow()) {
+      error_1(&e.to_string().into());
+    }
+
+    for (original, replacement) in PENDING_SHADERS.with_borrow_mut(std::mem::take) {
+      // objects keep previous programs on errors
+      match renderer.replace_shader(&original, &replacement) {
+        Ok(count) => {
+          let stats = renderer.backend.caches.borrow().stats();
+          log_1(&format!("relinked {count} objects, program cache {stats:?}").into());
+        }
+        Err(e) => error_1(&e.to_string().into()),
+      }
+    }
+
     if viewer::requested_rendering() {
-      triadica::paint_canvas(&context, &tree.borrow(), program_caches.clone());
+      let painted = match STEREO.get() {
+        Some(options) => renderer.paint_stereo(None, &tree.borrow(), &options),
+        None => glow
+          .paint(&mut renderer, &tree.borrow())
+          .and_then(|_| renderer.paint_viewports(None, &tree.borrow(), &[overview])),
+      };
+      if let Err(e) = painted {
+        error_1(&e.to_string().into());
+      }
+      for warning in renderer.take_warnings() {
+        warn_1(&warning.to_string().into());
+      }
 
       // document
       //   .query_selector(".debug")
@@ -60,6 +100,39 @@ pub fn init_app() -> Result<(), JsValue> {
   Ok(())
 }
 
+thread_local! {
+  /// shaders sent by `reloadShader`, as pairs of bundled and new sources, replaced before next frame
+  static PENDING_SHADERS: RefCell<Vec<(String, String)>> = const { RefCell::new(Vec::new()) };
+  /// set by `setStereo`, `None` for the mono view with glow
+  static STEREO: Cell<Option<StereoOptions>> = const { Cell::new(None) };
+}
+
+/// switch stereo mode, `side-by-side` for cardboard viewers, `anaglyph` for red/cyan glasses, `off` for mono view.
+/// `eye_separation` is in world units
+#[wasm_bindgen(js_name = setStereo)]
+pub fn set_stereo(mode: &str, eye_separation: f32) -> Result<(), JsValue> {
+  let options = match mode {
+    "side-by-side" => Some(StereoOptions::side_by_side()),
+    "anaglyph" => Some(StereoOptions::anaglyph()),
+    "off" => None,
+    _ => return Err(format!("unknown stereo mode `{mode}`").into()),
+  };
+  STEREO.set(options.map(|x| x.with_eye_separation(eye_separation)));
+  viewer::mark_dirty();
+  Ok(())
+}
+
+/// replace a shader of the demo at runtime, by file name like `demo.frag`.
+/// compile errors are logged when replacing, and objects keep drawing with the last working shader
+#[wasm_bindgen(js_name = reloadShader)]
+pub fn reload_shader(name: &str, source: &str) -> Result<(), JsValue> {
+  let original = container::bundled_shader(name)?;
+  let replacement = container::demo_shader(name, source)?;
+  PENDING_SHADERS.with_borrow_mut(|pending| pending.push((original, replacement)));
+  viewer::mark_dirty();
+  Ok(())
+}
+
 fn get_canvas() -> Element {
   let window = global_window();
   let document = window.document().expect("to get document");
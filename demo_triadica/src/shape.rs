@@ -1,8 +1,9 @@
 use quaternions::{qi, Quaternion};
-use triadica::{PackedAttrs, VertexDataValue};
+use triadica::{Indices, PackedAttrs, VertexDataValue};
 
+/// edges of a cube, 8 corners shared by 24 indices, draw with `DrawMode::Lines`
 #[allow(dead_code)]
-pub fn compute_cube_vertices() -> Vec<f32> {
+pub fn compute_cube_vertices() -> (PackedAttrs, Indices) {
   let geo: Vec<[f32; 3]> = vec![
     [-0.5, -0.5, 0.0],
     [-0.5, 0.5, 0.0],
@@ -15,17 +16,12 @@ pub fn compute_cube_vertices() -> Vec<f32> {
   ];
 
   let indices = vec![0, 1, 1, 2, 2, 3, 3, 0, 0, 4, 1, 5, 2, 6, 3, 7, 4, 5, 5, 6, 6, 7, 7, 4];
-  let mut points: Vec<[f32; 3]> = Vec::new();
-  for i in 0..indices.len() {
-    points.push(geo[indices[i]]);
-  }
 
-  let moved_points: Vec<_> = points.iter().map(|p| [p[0] * 400., p[1] * 400., p[2] * 400. - 1200.]).collect();
-  let mut vertices: Vec<f32> = Vec::new();
-  for p in moved_points {
-    vertices.extend_from_slice(&p);
-  }
-  vertices
+  let corners = geo
+    .iter()
+    .map(|p| PackedAttrs::Item(vec![VertexDataValue::Vec3([p[0] * 400., p[1] * 400., p[2] * 400. - 1200.])]))
+    .collect();
+  (PackedAttrs::List(corners), Indices::U16(indices))
 }
 
 type Q32 = Quaternion<f32>;
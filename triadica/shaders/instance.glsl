@@ -0,0 +1,13 @@
+// per-instance transform of `Instance`, scaled by `a_scale`, then rotated by `a_rotation`, then moved by `a_offset`.
+// attributes are declared by the including shader, since `attribute` and `in` differ between GLSL ES 1.00 and 3.00
+
+// rotate by a quaternion in `xyzw`, it is normalized first like on CPU
+vec3 rotate_by_quaternion(vec3 v, vec4 q) {
+  vec4 n = normalize(q);
+  vec3 t = 2.0 * cross(n.xyz, v);
+  return v + n.w * t + cross(n.xyz, t);
+}
+
+vec3 instance_transform(vec3 position, vec3 offset, float scale, vec4 rotation) {
+  return rotate_by_quaternion(position * scale, rotation) + offset;
+}
@@ -0,0 +1,20 @@
+precision mediump float;
+
+uniform vec3 u_near_color;
+uniform vec3 u_far_color;
+uniform float u_fade_range;
+
+varying float v_s;
+varying float v_r;
+
+void main() {
+  if (v_r >= 0.0) {
+    float factor = smoothstep(0.0, 0.4, 1.0 - v_r / u_fade_range);
+    gl_FragColor = vec4(mix(u_far_color, u_near_color, factor), 1.0);
+  } else if (v_r > -v_s) {
+    gl_FragColor = vec4(u_far_color, 1.0);
+  } else {
+    // supposed to be hidden with depth test
+    gl_FragColor = vec4(0.0, 0.0, 0.0, 1.0);
+  }
+}
@@ -0,0 +1,12 @@
+precision mediump float;
+
+uniform vec3 u_color;
+uniform vec3 u_light_direction;
+uniform float u_ambient;
+
+varying vec3 v_normal;
+
+void main() {
+  float diffuse = max(dot(normalize(v_normal), -normalize(u_light_direction)), 0.0);
+  gl_FragColor = vec4(u_color * (u_ambient + (1.0 - u_ambient) * diffuse), 1.0);
+}
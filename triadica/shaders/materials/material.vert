@@ -0,0 +1,36 @@
+attribute vec3 a_position;
+
+#ifdef USE_VERTEX_COLOR
+attribute vec4 a_color;
+varying vec4 v_color;
+#endif
+
+#ifdef USE_NORMAL
+attribute vec3 a_normal;
+varying vec3 v_normal;
+#endif
+
+uniform float u_point_size;
+
+varying float v_r;
+varying float v_s;
+
+#include "triadica/perspective"
+
+void main() {
+  PointResult result = transform_perspective(a_position);
+
+  v_s = result.s;
+  v_r = result.r;
+
+#ifdef USE_VERTEX_COLOR
+  v_color = a_color;
+#endif
+
+#ifdef USE_NORMAL
+  v_normal = a_normal;
+#endif
+
+  gl_PointSize = u_point_size;
+  gl_Position = vec4(result.point * 0.002, 1.0);
+}
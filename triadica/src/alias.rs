@@ -1,7 +1,10 @@
 use std::rc::Rc;
 
-use crate::component::{ComponentOptions, PackedAttrs, TriadicaElement};
-use crate::primes::{DrawMode, VertexData};
+use crate::component::{ComponentOptions, Instance, InstancedOptions, PackedAttrs, TriadicaElement};
+use crate::material::Material;
+use crate::primes::{DrawMode, Indices};
+use crate::state::RenderState;
+use crate::uniform::UniformMap;
 
 pub fn group(children: Vec<TriadicaElement>) -> TriadicaElement {
   TriadicaElement::Group(children)
@@ -13,7 +16,7 @@ pub fn object(
   fragment_shader: String,
   attr_names: Vec<(String, i8)>,
   packed_attrs: PackedAttrs,
-  get_uniforms: Rc<dyn Fn() -> VertexData>,
+  get_uniforms: Rc<dyn Fn() -> UniformMap>,
 ) -> TriadicaElement {
   TriadicaElement::Object(ComponentOptions {
     draw_mode,
@@ -21,6 +24,92 @@ pub fn object(
     fragment_shader,
     attr_names,
     packed_attrs,
+    indices: None,
+    textures: Vec::new(),
+    render_state: RenderState::default(),
+    point_size: 1.0,
+    culling: true,
     get_uniforms,
   })
 }
+
+/// object drawn with `draw_elements`, vertices are shared through indices
+pub fn indexed_object(
+  draw_mode: DrawMode,
+  vertex_shader: String,
+  fragment_shader: String,
+  attr_names: Vec<(String, i8)>,
+  packed_attrs: PackedAttrs,
+  indices: Indices,
+  get_uniforms: Rc<dyn Fn() -> UniformMap>,
+) -> TriadicaElement {
+  TriadicaElement::Object(ComponentOptions {
+    draw_mode,
+    vertex_shader,
+    fragment_shader,
+    attr_names,
+    packed_attrs,
+    indices: Some(indices),
+    textures: Vec::new(),
+    render_state: RenderState::default(),
+    point_size: 1.0,
+    culling: true,
+    get_uniforms,
+  })
+}
+
+/// object drawn once for each instance, shaders read `Instance::attr_names` besides per-vertex attributes,
+/// and should place vertices with `instance_transform` of `INSTANCE_SNIPPET` to match picking, bounds and the software renderer
+pub fn instanced_object(
+  draw_mode: DrawMode,
+  vertex_shader: String,
+  fragment_shader: String,
+  attr_names: Vec<(String, i8)>,
+  packed_attrs: PackedAttrs,
+  instances: &[Instance],
+  get_uniforms: Rc<dyn Fn() -> UniformMap>,
+) -> TriadicaElement {
+  TriadicaElement::Instanced(InstancedOptions {
+    component: ComponentOptions {
+      draw_mode,
+      vertex_shader,
+      fragment_shader,
+      attr_names,
+      packed_attrs,
+      indices: None,
+      textures: Vec::new(),
+      render_state: RenderState::default(),
+      point_size: 1.0,
+      culling: true,
+      get_uniforms,
+    },
+    instance_attr_names: Instance::attr_names(),
+    instance_attrs: Instance::pack(instances),
+  })
+}
+
+/// object drawn with `DrawMode::Points`, `point_size` is bound to `u_point_size`.
+/// add `a_point_size` to `attr_names` for sizes varying per vertex
+pub fn points_object(
+  vertex_shader: String,
+  fragment_shader: String,
+  attr_names: Vec<(String, i8)>,
+  packed_attrs: PackedAttrs,
+  point_size: f32,
+  get_uniforms: Rc<dyn Fn() -> UniformMap>,
+) -> TriadicaElement {
+  object(
+    DrawMode::Points,
+    vertex_shader,
+    fragment_shader,
+    attr_names,
+    packed_attrs,
+    get_uniforms,
+  )
+  .with_point_size(point_size)
+}
+
+/// object drawn with a built-in material, `packed_attrs` items follow `Material::attr_names`
+pub fn material_object(material: &Material, draw_mode: DrawMode, packed_attrs: PackedAttrs) -> TriadicaElement {
+  TriadicaElement::Object(material.component(draw_mode, packed_attrs))
+}
@@ -0,0 +1,686 @@
+//! abstraction over the graphics API, so that rendering logic can run without a WebGL context
+
+use web_sys::WebGl2RenderingContext;
+
+use crate::error::{ShaderStage, TriadicaError};
+use crate::glsl::{Declaration, ShaderDeclarations};
+use crate::primes::{DrawMode, IndexType, Indices};
+use crate::program::ProgramKey;
+use crate::reflect::{ActiveVariable, ProgramReflection};
+use crate::state::RenderState;
+use crate::texture::{Texture, TextureOptions};
+use crate::uniform::UniformValue;
+
+/// operations `Renderer` needs from a graphics API
+pub trait RenderBackend {
+  /// handle of a linked shader program
+  type Program: Clone;
+  /// handle of a vertex buffer on GPU
+  type Buffer;
+  /// handle of a vertex array object, remembering attribute bindings
+  type VertexArray;
+  /// handle of an uploaded texture
+  type GpuTexture;
+  /// offscreen framebuffer with a color texture, and optionally a depth attachment
+  type RenderTarget;
+
+  /// clear color and depth of current render target
+  fn clear(&mut self);
+
+  /// size of the canvas in pixels
+  fn drawing_buffer_size(&self) -> (i32, i32);
+
+  fn create_render_target(&mut self, width: i32, height: i32, depth: bool) -> Result<Self::RenderTarget, TriadicaError>;
+
+  fn delete_render_target(&mut self, target: Self::RenderTarget);
+
+  /// draw into a render target, `None` for the canvas, viewport is set to cover it
+  fn bind_render_target(&mut self, target: Option<&Self::RenderTarget>);
+
+  /// draw and clear only within a rectangle of the bound target, in pixels from the bottom left.
+  /// binding a target resets it to cover the whole target
+  fn set_viewport(&mut self, x: i32, y: i32, width: i32, height: i32);
+
+  /// channels written by drawing and clearing
+  fn set_color_mask(&mut self, red: bool, green: bool, blue: bool, alpha: bool);
+
+  /// RGBA of one pixel of the bound render target, in pixels from the bottom left
+  fn read_pixel(&mut self, x: i32, y: i32) -> Result<[u8; 4], TriadicaError>;
+
+  /// color texture of a render target, for sampling in later passes
+  fn target_texture<'a>(&self, target: &'a Self::RenderTarget) -> &'a Self::GpuTexture;
+
+  /// compile and link a program, backends may return a cached one of same key.
+  /// each call counts a user of the program, until `release_program`
+  fn create_program(&mut self, key: ProgramKey, vert_shader: &str, frag_shader: &str) -> Result<Self::Program, TriadicaError>;
+
+  /// active attributes and uniforms of a linked program
+  fn reflection<'a>(&'a self, program: &'a Self::Program) -> &'a ProgramReflection;
+
+  fn use_program(&mut self, program: &Self::Program);
+
+  /// a user of the program is gone, backends may delete programs nobody uses
+  fn release_program(&mut self, program: Self::Program);
+
+  /// like `release_program`, for a program whose sources are replaced and not linked again,
+  /// backends delete it once nobody uses it instead of keeping it for reuse
+  fn retire_program(&mut self, program: Self::Program);
+
+  /// drop cached GPU objects without deleting them, after the context is lost and they are invalid
+  fn forget_resources(&mut self);
+
+  /// enable or disable blending, depth test, depth writes, culling and polygon offset
+  fn set_render_state(&mut self, state: &RenderState);
+
+  /// upload vertex data into a new buffer
+  fn create_buffer(&mut self, data: &[f32]) -> Result<Self::Buffer, TriadicaError>;
+
+  /// upload indices into a new element buffer, bound to current vertex array
+  fn create_index_buffer(&mut self, indices: &Indices) -> Result<Self::Buffer, TriadicaError>;
+
+  /// bind an index buffer to current vertex array, like `create_index_buffer` does for new ones
+  fn bind_index_buffer(&mut self, buffer: &Self::Buffer);
+
+  fn delete_buffer(&mut self, buffer: Self::Buffer);
+
+  fn create_vertex_array(&mut self) -> Result<Self::VertexArray, TriadicaError>;
+
+  /// bind a vertex array, attribute pointers are recorded into it, `None` to unbind
+  fn bind_vertex_array(&mut self, vertex_array: Option<&Self::VertexArray>);
+
+  fn delete_vertex_array(&mut self, vertex_array: Self::VertexArray);
+
+  /// point an attribute to a buffer at its location in the program, with `unit_size` floats per vertex.
+  /// `divisor` 0 advances per vertex, 1 advances per instance.
+  /// fails when the attribute is not in `reflection` of the program, not declared or optimized out
+  fn attrib_pointer(
+    &mut self,
+    program: &Self::Program,
+    attr_name: &str,
+    buffer: &Self::Buffer,
+    unit_size: i32,
+    divisor: u32,
+  ) -> Result<(), TriadicaError>;
+
+  /// upload pixels of a texture, apply its filtering and wrapping, and generate mipmaps when asked
+  fn create_texture(&mut self, texture: &Texture) -> Result<Self::GpuTexture, TriadicaError>;
+
+  fn delete_texture(&mut self, texture: Self::GpuTexture);
+
+  /// bind a texture to a texture unit, `None` to unbind
+  fn bind_texture(&mut self, unit: u32, texture: Option<&Self::GpuTexture>);
+
+  /// fails when the value can not be bound, like arrays mixing kinds
+  fn set_uniform(&mut self, program: &Self::Program, name: &str, value: &UniformValue) -> Result<(), TriadicaError>;
+
+  /// draw `count` vertices from `first` with the bound vertex array
+  fn draw(&mut self, mode: DrawMode, first: i32, count: i32);
+
+  /// draw `count` indices of the element buffer in the bound vertex array
+  fn draw_elements(&mut self, mode: DrawMode, count: i32, index_type: IndexType);
+
+  /// like `draw`, repeated for `instance_count` instances
+  fn draw_instanced(&mut self, mode: DrawMode, first: i32, count: i32, instance_count: i32);
+
+  /// like `draw_elements`, repeated for `instance_count` instances
+  fn draw_elements_instanced(&mut self, mode: DrawMode, count: i32, index_type: IndexType, instance_count: i32);
+}
+
+/// a call made to the `RecordingBackend`, programs, buffers and vertex arrays are referred by index
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackendCall {
+  Clear,
+  CreateProgram {
+    program: usize,
+    vertex_shader: String,
+    fragment_shader: String,
+  },
+  UseProgram(usize),
+  DeleteProgram(usize),
+  SetRenderState(RenderState),
+  CreateBuffer {
+    buffer: usize,
+    data: Vec<f32>,
+  },
+  CreateIndexBuffer {
+    buffer: usize,
+    indices: Indices,
+  },
+  BindIndexBuffer(usize),
+  DeleteBuffer(usize),
+  CreateVertexArray(usize),
+  BindVertexArray(Option<usize>),
+  DeleteVertexArray(usize),
+  AttribPointer {
+    program: usize,
+    attr_name: String,
+    buffer: usize,
+    unit_size: i32,
+    divisor: u32,
+  },
+  CreateTexture {
+    texture: usize,
+    /// `None` for image elements
+    size: Option<(u32, u32)>,
+    options: TextureOptions,
+  },
+  DeleteTexture(usize),
+  CreateRenderTarget {
+    target: usize,
+    texture: usize,
+    width: i32,
+    height: i32,
+    depth: bool,
+  },
+  DeleteRenderTarget(usize),
+  BindRenderTarget(Option<usize>),
+  /// red, green, blue and alpha
+  SetColorMask([bool; 4]),
+  SetViewport {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+  },
+  ReadPixel {
+    x: i32,
+    y: i32,
+  },
+  BindTexture {
+    unit: u32,
+    texture: Option<usize>,
+  },
+  SetUniform {
+    program: usize,
+    name: String,
+    value: UniformValue,
+  },
+  Draw {
+    mode: DrawMode,
+    first: i32,
+    count: i32,
+  },
+  DrawElements {
+    mode: DrawMode,
+    count: i32,
+    index_type: IndexType,
+  },
+  DrawInstanced {
+    mode: DrawMode,
+    first: i32,
+    count: i32,
+    instance_count: i32,
+  },
+  DrawElementsInstanced {
+    mode: DrawMode,
+    count: i32,
+    index_type: IndexType,
+    instance_count: i32,
+  },
+}
+
+/// content of a buffer created in `RecordingBackend`
+#[derive(Debug, Clone, PartialEq)]
+enum RecordedBuffer {
+  Vertices(Vec<f32>),
+  Indices(Indices),
+}
+
+/// render target of `RecordingBackend`, with index of its color texture
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordedTarget {
+  pub id: usize,
+  pub texture: usize,
+}
+
+/// backend that only logs every call, for inspecting rendering in native tests
+#[derive(Debug)]
+pub struct RecordingBackend {
+  pub calls: Vec<BackendCall>,
+  /// reported as drawing buffer size
+  pub canvas_size: (i32, i32),
+  /// returned by `read_pixel`, as if drawn there
+  pub pixel: [u8; 4],
+  /// attributes left out of reflections of programs created later, like a GLSL compiler dropping unused ones
+  pub optimized_out: Vec<String>,
+  /// sources of programs created, index is the program handle
+  programs: Vec<(String, String)>,
+  /// declarations read from sources of programs, in same order
+  reflections: Vec<ProgramReflection>,
+  /// keys of programs created, in same order
+  program_keys: Vec<ProgramKey>,
+  /// users of programs created, deleted at 0
+  program_users: Vec<usize>,
+  /// data of buffers created, `None` after deleted
+  buffers: Vec<Option<RecordedBuffer>>,
+  /// vertex arrays created, `false` after deleted
+  vertex_arrays: Vec<bool>,
+  /// textures created, `false` after deleted
+  textures: Vec<bool>,
+  /// render targets created, `false` after deleted
+  render_targets: Vec<bool>,
+}
+
+impl Default for RecordingBackend {
+  fn default() -> Self {
+    Self {
+      calls: Vec::new(),
+      canvas_size: (800, 600),
+      pixel: [0, 0, 0, 255],
+      optimized_out: Vec::new(),
+      programs: Vec::new(),
+      reflections: Vec::new(),
+      program_keys: Vec::new(),
+      program_users: Vec::new(),
+      buffers: Vec::new(),
+      vertex_arrays: Vec::new(),
+      textures: Vec::new(),
+      render_targets: Vec::new(),
+    }
+  }
+}
+
+impl RecordingBackend {
+  /// take recorded calls and start over, created programs are kept
+  pub fn take_calls(&mut self) -> Vec<BackendCall> {
+    std::mem::take(&mut self.calls)
+  }
+
+  /// draw calls in order, as `(mode, first, count)`, `first` is 0 for `draw_elements`.
+  /// instanced draws are included, see `instanced_draw_calls` for instance counts
+  pub fn draw_calls(&self) -> Vec<(DrawMode, i32, i32)> {
+    self
+      .calls
+      .iter()
+      .filter_map(|call| match call {
+        BackendCall::Draw { mode, first, count } | BackendCall::DrawInstanced { mode, first, count, .. } => {
+          Some((*mode, *first, *count))
+        }
+        BackendCall::DrawElements { mode, count, .. } | BackendCall::DrawElementsInstanced { mode, count, .. } => {
+          Some((*mode, 0, *count))
+        }
+        _ => None,
+      })
+      .collect()
+  }
+
+  /// instanced draw calls in order, as `(mode, count, instance_count)`
+  pub fn instanced_draw_calls(&self) -> Vec<(DrawMode, i32, i32)> {
+    self
+      .calls
+      .iter()
+      .filter_map(|call| match call {
+        BackendCall::DrawInstanced {
+          mode,
+          count,
+          instance_count,
+          ..
+        }
+        | BackendCall::DrawElementsInstanced {
+          mode,
+          count,
+          instance_count,
+          ..
+        } => Some((*mode, *count, *instance_count)),
+        _ => None,
+      })
+      .collect()
+  }
+
+  /// values set to a uniform in order, from all programs
+  pub fn uniform_values(&self, name: &str) -> Vec<UniformValue> {
+    self
+      .calls
+      .iter()
+      .filter_map(|call| match call {
+        BackendCall::SetUniform { name: n, value, .. } if n == name => Some(value.to_owned()),
+        _ => None,
+      })
+      .collect()
+  }
+
+  /// data of buffers pointed to an attribute in order, from all programs
+  pub fn buffer_data(&self, attr_name: &str) -> Vec<Vec<f32>> {
+    self
+      .calls
+      .iter()
+      .filter_map(|call| match call {
+        BackendCall::AttribPointer { attr_name: n, buffer, .. } if n == attr_name => match self.buffers.get(*buffer) {
+          Some(Some(RecordedBuffer::Vertices(data))) => Some(data.to_owned()),
+          _ => None,
+        },
+        _ => None,
+      })
+      .collect()
+  }
+
+  /// indices uploaded in order
+  pub fn index_data(&self) -> Vec<Indices> {
+    self
+      .buffers
+      .iter()
+      .filter_map(|buffer| match buffer {
+        Some(RecordedBuffer::Indices(indices)) => Some(indices.to_owned()),
+        _ => None,
+      })
+      .collect()
+  }
+
+  /// number of buffers created and not deleted yet
+  pub fn live_buffers(&self) -> usize {
+    self.buffers.iter().filter(|b| b.is_some()).count()
+  }
+
+  /// number of vertex arrays created and not deleted yet
+  pub fn live_vertex_arrays(&self) -> usize {
+    self.vertex_arrays.iter().filter(|v| **v).count()
+  }
+
+  /// number of textures created and not deleted yet
+  pub fn live_textures(&self) -> usize {
+    self.textures.iter().filter(|t| **t).count()
+  }
+
+  /// number of render targets created and not deleted yet
+  pub fn live_render_targets(&self) -> usize {
+    self.render_targets.iter().filter(|t| **t).count()
+  }
+
+  /// number of programs created and not deleted yet
+  pub fn live_programs(&self) -> usize {
+    self.program_users.iter().filter(|users| **users > 0).count()
+  }
+
+  /// shader sources of a program handle
+  pub fn program_sources(&self, program: usize) -> Option<&(String, String)> {
+    self.programs.get(program)
+  }
+}
+
+impl RenderBackend for RecordingBackend {
+  type Program = usize;
+  type Buffer = usize;
+  type VertexArray = usize;
+  type GpuTexture = usize;
+  type RenderTarget = RecordedTarget;
+
+  fn clear(&mut self) {
+    self.calls.push(BackendCall::Clear);
+  }
+
+  fn drawing_buffer_size(&self) -> (i32, i32) {
+    self.canvas_size
+  }
+
+  fn create_render_target(&mut self, width: i32, height: i32, depth: bool) -> Result<RecordedTarget, TriadicaError> {
+    let target = RecordedTarget {
+      id: self.render_targets.len(),
+      texture: self.textures.len(),
+    };
+    self.render_targets.push(true);
+    self.textures.push(true);
+    self.calls.push(BackendCall::CreateRenderTarget {
+      target: target.id,
+      texture: target.texture,
+      width,
+      height,
+      depth,
+    });
+    Ok(target)
+  }
+
+  fn delete_render_target(&mut self, target: RecordedTarget) {
+    self.render_targets[target.id] = false;
+    self.textures[target.texture] = false;
+    self.calls.push(BackendCall::DeleteRenderTarget(target.id));
+  }
+
+  fn bind_render_target(&mut self, target: Option<&RecordedTarget>) {
+    self.calls.push(BackendCall::BindRenderTarget(target.map(|t| t.id)));
+  }
+
+  fn set_viewport(&mut self, x: i32, y: i32, width: i32, height: i32) {
+    self.calls.push(BackendCall::SetViewport { x, y, width, height });
+  }
+
+  fn set_color_mask(&mut self, red: bool, green: bool, blue: bool, alpha: bool) {
+    self.calls.push(BackendCall::SetColorMask([red, green, blue, alpha]));
+  }
+
+  fn read_pixel(&mut self, x: i32, y: i32) -> Result<[u8; 4], TriadicaError> {
+    self.calls.push(BackendCall::ReadPixel { x, y });
+    Ok(self.pixel)
+  }
+
+  fn target_texture<'a>(&self, target: &'a RecordedTarget) -> &'a usize {
+    &target.texture
+  }
+
+  fn create_program(&mut self, key: ProgramKey, vert_shader: &str, frag_shader: &str) -> Result<usize, TriadicaError> {
+    let cached = self
+      .program_keys
+      .iter()
+      .zip(&self.program_users)
+      .zip(&self.programs)
+      .position(|((k, users), (vert, frag))| *users > 0 && *k == key && vert == vert_shader && frag == frag_shader);
+    if let Some(idx) = cached {
+      self.program_users[idx] += 1;
+      return Ok(idx);
+    }
+    let program = self.programs.len();
+    self.programs.push((vert_shader.to_owned(), frag_shader.to_owned()));
+    let mut reflection = reflect_sources(vert_shader, frag_shader);
+    reflection.attributes.retain(|name, _| !self.optimized_out.contains(name));
+    self.reflections.push(reflection);
+    self.program_keys.push(key);
+    self.program_users.push(1);
+    self.calls.push(BackendCall::CreateProgram {
+      program,
+      vertex_shader: vert_shader.to_owned(),
+      fragment_shader: frag_shader.to_owned(),
+    });
+    Ok(program)
+  }
+
+  fn reflection<'a>(&'a self, program: &'a usize) -> &'a ProgramReflection {
+    &self.reflections[*program]
+  }
+
+  fn use_program(&mut self, program: &usize) {
+    self.calls.push(BackendCall::UseProgram(*program));
+  }
+
+  fn release_program(&mut self, program: usize) {
+    let users = &mut self.program_users[program];
+    *users = users.saturating_sub(1);
+    if *users == 0 {
+      self.calls.push(BackendCall::DeleteProgram(program));
+    }
+  }
+
+  fn retire_program(&mut self, program: usize) {
+    // programs are deleted at 0 users anyway
+    self.release_program(program);
+  }
+
+  fn forget_resources(&mut self) {
+    self.program_users.fill(0);
+    self.buffers.fill(None);
+    self.vertex_arrays.fill(false);
+    self.textures.fill(false);
+    self.render_targets.fill(false);
+  }
+
+  fn set_render_state(&mut self, state: &RenderState) {
+    self.calls.push(BackendCall::SetRenderState(*state));
+  }
+
+  fn create_buffer(&mut self, data: &[f32]) -> Result<usize, TriadicaError> {
+    let buffer = self.buffers.len();
+    self.buffers.push(Some(RecordedBuffer::Vertices(data.to_owned())));
+    self.calls.push(BackendCall::CreateBuffer {
+      buffer,
+      data: data.to_owned(),
+    });
+    Ok(buffer)
+  }
+
+  fn create_index_buffer(&mut self, indices: &Indices) -> Result<usize, TriadicaError> {
+    let buffer = self.buffers.len();
+    self.buffers.push(Some(RecordedBuffer::Indices(indices.to_owned())));
+    self.calls.push(BackendCall::CreateIndexBuffer {
+      buffer,
+      indices: indices.to_owned(),
+    });
+    Ok(buffer)
+  }
+
+  fn bind_index_buffer(&mut self, buffer: &usize) {
+    self.calls.push(BackendCall::BindIndexBuffer(*buffer));
+  }
+
+  fn delete_buffer(&mut self, buffer: usize) {
+    self.buffers[buffer] = None;
+    self.calls.push(BackendCall::DeleteBuffer(buffer));
+  }
+
+  fn create_vertex_array(&mut self) -> Result<usize, TriadicaError> {
+    let vertex_array = self.vertex_arrays.len();
+    self.vertex_arrays.push(true);
+    self.calls.push(BackendCall::CreateVertexArray(vertex_array));
+    Ok(vertex_array)
+  }
+
+  fn bind_vertex_array(&mut self, vertex_array: Option<&usize>) {
+    self.calls.push(BackendCall::BindVertexArray(vertex_array.copied()));
+  }
+
+  fn delete_vertex_array(&mut self, vertex_array: usize) {
+    self.vertex_arrays[vertex_array] = false;
+    self.calls.push(BackendCall::DeleteVertexArray(vertex_array));
+  }
+
+  fn attrib_pointer(
+    &mut self,
+    program: &usize,
+    attr_name: &str,
+    buffer: &usize,
+    unit_size: i32,
+    divisor: u32,
+  ) -> Result<(), TriadicaError> {
+    let reflection = self
+      .reflections
+      .get(*program)
+      .ok_or_else(|| TriadicaError::Other(format!("unknown program {program}")))?;
+    if !reflection.has_attribute(attr_name) {
+      return Err(TriadicaError::AttributeMismatch(format!(
+        "attribute `{attr_name}` is not declared in vertex shader, or optimized out since unused"
+      )));
+    }
+    self.calls.push(BackendCall::AttribPointer {
+      program: *program,
+      attr_name: attr_name.to_owned(),
+      buffer: *buffer,
+      unit_size,
+      divisor,
+    });
+    Ok(())
+  }
+
+  fn create_texture(&mut self, texture: &Texture) -> Result<usize, TriadicaError> {
+    let handle = self.textures.len();
+    self.textures.push(true);
+    self.calls.push(BackendCall::CreateTexture {
+      texture: handle,
+      size: texture.source.size(),
+      options: texture.options,
+    });
+    Ok(handle)
+  }
+
+  fn delete_texture(&mut self, texture: usize) {
+    self.textures[texture] = false;
+    self.calls.push(BackendCall::DeleteTexture(texture));
+  }
+
+  fn bind_texture(&mut self, unit: u32, texture: Option<&usize>) {
+    self.calls.push(BackendCall::BindTexture {
+      unit,
+      texture: texture.copied(),
+    });
+  }
+
+  fn set_uniform(&mut self, program: &usize, name: &str, value: &UniformValue) -> Result<(), TriadicaError> {
+    value.kind()?;
+    self.calls.push(BackendCall::SetUniform {
+      program: *program,
+      name: name.to_owned(),
+      value: value.to_owned(),
+    });
+    Ok(())
+  }
+
+  fn draw(&mut self, mode: DrawMode, first: i32, count: i32) {
+    self.calls.push(BackendCall::Draw { mode, first, count });
+  }
+
+  fn draw_elements(&mut self, mode: DrawMode, count: i32, index_type: IndexType) {
+    self.calls.push(BackendCall::DrawElements { mode, count, index_type });
+  }
+
+  fn draw_instanced(&mut self, mode: DrawMode, first: i32, count: i32, instance_count: i32) {
+    self.calls.push(BackendCall::DrawInstanced {
+      mode,
+      first,
+      count,
+      instance_count,
+    });
+  }
+
+  fn draw_elements_instanced(&mut self, mode: DrawMode, count: i32, index_type: IndexType, instance_count: i32) {
+    self.calls.push(BackendCall::DrawElementsInstanced {
+      mode,
+      count,
+      index_type,
+      instance_count,
+    });
+  }
+}
+
+/// attributes of vertex shader and uniforms of both shaders, read from declarations in sources
+fn reflect_sources(vert_shader: &str, frag_shader: &str) -> ProgramReflection {
+  let vertex = ShaderDeclarations::parse(vert_shader, ShaderStage::Vertex);
+  let fragment = ShaderDeclarations::parse(frag_shader, ShaderStage::Fragment);
+  let variable = |declaration: &Declaration| ActiveVariable {
+    gl_type: gl_type_of(&declaration.type_name),
+    size: declaration.array_len.unwrap_or(1) as i32,
+  };
+  let mut reflection = ProgramReflection::default();
+  for declaration in &vertex.attributes {
+    reflection.attributes.insert(declaration.name.to_owned(), variable(declaration));
+  }
+  for declaration in vertex.uniforms.iter().chain(&fragment.uniforms) {
+    reflection.uniforms.insert(declaration.name.to_owned(), variable(declaration));
+  }
+  reflection
+}
+
+/// GL enum of a GLSL type name, 0 for types not listed
+fn gl_type_of(type_name: &str) -> u32 {
+  match type_name {
+    "float" => WebGl2RenderingContext::FLOAT,
+    "vec2" => WebGl2RenderingContext::FLOAT_VEC2,
+    "vec3" => WebGl2RenderingContext::FLOAT_VEC3,
+    "vec4" => WebGl2RenderingContext::FLOAT_VEC4,
+    "int" => WebGl2RenderingContext::INT,
+    "ivec2" => WebGl2RenderingContext::INT_VEC2,
+    "ivec3" => WebGl2RenderingContext::INT_VEC3,
+    "ivec4" => WebGl2RenderingContext::INT_VEC4,
+    "bool" => WebGl2RenderingContext::BOOL,
+    "mat2" => WebGl2RenderingContext::FLOAT_MAT2,
+    "mat3" => WebGl2RenderingContext::FLOAT_MAT3,
+    "mat4" => WebGl2RenderingContext::FLOAT_MAT4,
+    "sampler2D" => WebGl2RenderingContext::SAMPLER_2D,
+    _ => 0,
+  }
+}
@@ -0,0 +1,174 @@
+//! bounding spheres of objects, so objects outside the view cone are skipped before drawing
+
+use glam::{Affine3A, Vec3};
+
+use crate::component::ComponentCache;
+use crate::raster::{instance_transform, positions_of};
+use crate::viewer::{Camera, CLIP_SCALE};
+
+/// sphere containing every position of an object
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+  pub center: Vec3,
+  pub radius: f32,
+}
+
+impl Bounds {
+  /// sphere around the box of the points, `None` without points
+  pub fn of_points<I: IntoIterator<Item = Vec3>>(points: I) -> Option<Self> {
+    let points: Vec<Vec3> = points.into_iter().collect();
+    let first = *points.first()?;
+    let (min, max) = points.iter().fold((first, first), |(min, max), p| (min.min(*p), max.max(*p)));
+    let center = (min + max) * 0.5;
+    let radius = points.iter().fold(0.0_f32, |radius, p| radius.max(p.distance(center)));
+    Some(Self { center, radius })
+  }
+
+  /// bounds of positions from `a_position`, moved by each instance for instanced objects.
+  /// `None` for objects without positions, they are never culled
+  pub fn of_object(item: &ComponentCache) -> Option<Self> {
+    let base = Self::of_points(positions_of(item).ok()??)?;
+    match &item.instances {
+      Some(instances) => (0..instances.count)
+        .map(|idx| base.transformed(&instance_transform(instances, idx)))
+        .reduce(|a, b| a.union(&b)),
+      None => Some(base),
+    }
+  }
+
+  /// sphere containing this one after a transform
+  pub fn transformed(&self, transform: &Affine3A) -> Self {
+    let m = transform.matrix3;
+    let scale = m.x_axis.length().max(m.y_axis.length()).max(m.z_axis.length());
+    Self {
+      center: transform.transform_point3(self.center),
+      radius: self.radius * scale,
+    }
+  }
+
+  /// smallest sphere containing both
+  pub fn union(&self, other: &Self) -> Self {
+    let distance = self.center.distance(other.center);
+    if distance + other.radius <= self.radius {
+      return *self;
+    }
+    if distance + self.radius <= other.radius {
+      return *other;
+    }
+    let radius = (distance + self.radius + other.radius) * 0.5;
+    let direction = (other.center - self.center) / distance;
+    Self {
+      center: self.center + direction * (radius - self.radius),
+      radius,
+    }
+  }
+
+  /// whether any part of the sphere is in the view cone of the camera, between the near cut at `r = -0.9 * coneBackScale`
+  /// where the vertex shader hides points behind the viewer, and the far plane of clip space.
+  /// edges of the screen are planes through the apex of the cone, `coneBackScale * lookDistance` behind the camera
+  pub fn is_visible(&self, camera: &Camera) -> bool {
+    let s = camera.cone_back_scale;
+    let look = camera.look_distance;
+    let apex = camera.position - camera.forward * (s * look);
+    let moved = self.center - apex;
+    // distance along `forward` from the apex, `r = t / lookDistance - s`
+    let t = moved.dot(camera.forward);
+    let near = 0.1 * s * look;
+    let far = (1.0 / CLIP_SCALE + s) * look;
+    if t + self.radius < near || t - self.radius > far {
+      return false;
+    }
+    // edges of the screen are at `x = k * t` and `y = k * t * viewportRatio`, see `Camera::ray_at`
+    let k = 1.0 / (CLIP_SCALE * look * (s + 1.0));
+    let x = moved.dot(camera.rightward);
+    let y = moved.dot(camera.upward);
+    let outside = |offset: f32, slope: f32| (offset - slope * t) / (1.0 + slope * slope).sqrt() > self.radius;
+    let ky = k * camera.viewport_ratio;
+    !(outside(x, k) || outside(-x, k) || outside(y, ky) || outside(-y, ky))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// at `z = 600` facing the origin, with `x` of screen along `-x` of world
+  fn camera() -> Camera {
+    Camera::looking_at(Vec3::new(0.0, 0.0, 600.0), Vec3::ZERO, Vec3::Y)
+  }
+
+  fn sphere(x: f32, y: f32, z: f32, radius: f32) -> Bounds {
+    Bounds {
+      center: Vec3::new(x, y, z),
+      radius,
+    }
+  }
+
+  /// whether a point is drawn, inside clip space and in front of the near cut
+  fn projected_inside(camera: &Camera, p: Vec3) -> bool {
+    let perspective = camera.transform_perspective(p);
+    let clip = camera.clip_position(p);
+    perspective.r >= perspective.s * -0.9 && clip.x.abs() <= 1.0 && clip.y.abs() <= 1.0 && clip.z <= 1.0
+  }
+
+  #[test]
+  fn sees_spheres_in_front() {
+    let camera = camera();
+    assert!(sphere(0.0, 0.0, 0.0, 10.0).is_visible(&camera));
+    assert!(sphere(0.0, 0.0, -10000.0, 10.0).is_visible(&camera));
+    // half of the screen is 500 wide at the origin, 900 in front of the apex
+    assert!(sphere(480.0, 0.0, 0.0, 10.0).is_visible(&camera));
+  }
+
+  #[test]
+  fn culls_spheres_out_of_the_cone() {
+    let camera = camera();
+    assert!(!sphere(0.0, 0.0, 2000.0, 100.0).is_visible(&camera), "behind the camera");
+    assert!(!sphere(600.0, 0.0, 0.0, 50.0).is_visible(&camera), "beside the screen");
+    assert!(!sphere(0.0, -700.0, 0.0, 50.0).is_visible(&camera), "below the screen");
+    assert!(!sphere(0.0, 0.0, -400000.0, 1000.0).is_visible(&camera), "beyond the far plane");
+    // reaching into the screen from beside it
+    assert!(sphere(600.0, 0.0, 0.0, 100.0).is_visible(&camera));
+  }
+
+  #[test]
+  fn never_culls_spheres_with_points_on_screen() {
+    let camera = Camera {
+      viewport_ratio: 0.75,
+      ..Camera::looking_at(Vec3::new(100.0, 200.0, 300.0), Vec3::new(-200.0, 0.0, -400.0), Vec3::Y)
+    };
+    let directions: Vec<Vec3> = (0..64)
+      .map(|i| {
+        let (a, b) = (i as f32 * 0.7, i as f32 * 1.3);
+        Vec3::new(a.cos() * b.sin(), a.sin() * b.sin(), b.cos())
+      })
+      .collect();
+    for x in (-2000..=2000).step_by(250) {
+      for z in (-3000..=1500).step_by(250) {
+        let bounds = sphere(x as f32, 100.0, z as f32, 120.0);
+        let seen = directions
+          .iter()
+          .any(|d| projected_inside(&camera, bounds.center + *d * bounds.radius));
+        assert!(!seen || bounds.is_visible(&camera), "{bounds:?} is on screen");
+      }
+    }
+  }
+
+  #[test]
+  fn combines_spheres() {
+    let points = [Vec3::new(-1.0, 0.0, 0.0), Vec3::new(3.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 0.0)];
+    assert_eq!(Bounds::of_points(points), Some(sphere(1.0, 0.5, 0.0, 4.25_f32.sqrt())));
+    assert_eq!(Bounds::of_points(Vec::new()), None);
+
+    let a = sphere(0.0, 0.0, 0.0, 1.0);
+    assert_eq!(a.union(&sphere(0.5, 0.0, 0.0, 0.2)), a);
+    assert_eq!(a.union(&sphere(4.0, 0.0, 0.0, 1.0)), sphere(2.0, 0.0, 0.0, 3.0));
+
+    let moved = a.transformed(&Affine3A::from_scale_rotation_translation(
+      Vec3::new(1.0, 3.0, 2.0),
+      glam::Quat::IDENTITY,
+      Vec3::X,
+    ));
+    assert_eq!(moved, sphere(1.0, 0.0, 0.0, 3.0));
+  }
+}
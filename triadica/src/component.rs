@@ -1,7 +1,19 @@
-use std::{fmt::Debug, rc::Rc};
+use std::{
+  collections::HashMap,
+  fmt::Debug,
+  rc::Rc,
+  sync::atomic::{AtomicU64, Ordering},
+};
 
 use crate::{
-  primes::{DrawMode, VertexData},
+  bounds::Bounds,
+  error::{ShaderStage, TriadicaError},
+  glsl::{check_attributes, check_stages, ShaderDeclarations},
+  primes::{DrawMode, Indices, VertexData},
+  program::ProgramKey,
+  state::RenderState,
+  texture::Texture,
+  uniform::UniformMap,
   VertexDataValue,
 };
 
@@ -10,11 +22,13 @@ use crate::{
 pub enum TriadicaElement {
   Group(Vec<TriadicaElement>),
   Object(ComponentOptions),
+  /// same geometry drawn once per instance, in a single draw call
+  Instanced(InstancedOptions),
 }
 
 impl TriadicaElement {
   /// compile from markup to data for webgl program
-  pub fn compile_to_tree(&self) -> Result<TriadicaElementTree, String> {
+  pub fn compile_to_tree(&self) -> Result<TriadicaElementTree, TriadicaError> {
     match self {
       TriadicaElement::Group(children) => {
         let children = children
@@ -23,8 +37,66 @@ impl TriadicaElement {
           .collect::<Result<Vec<_>, _>>()?;
         Ok(TriadicaElementTree::Group(children))
       }
-      TriadicaElement::Object(component) => Ok(TriadicaElementTree::Object(component.compile_attributes())),
+      TriadicaElement::Object(component) => {
+        component.validate()?;
+        Ok(TriadicaElementTree::Object(Box::new(component.compile_attributes())))
+      }
+      TriadicaElement::Instanced(options) => {
+        options.validate()?;
+        Ok(TriadicaElementTree::Object(Box::new(options.compile_attributes()?)))
+      }
+    }
+  }
+
+  /// element at a path of child indices, like `PickHit::path`. an empty path is the element itself
+  pub fn at(&self, path: &[usize]) -> Option<&TriadicaElement> {
+    match (path, self) {
+      ([], _) => Some(self),
+      ([idx, rest @ ..], TriadicaElement::Group(children)) => children.get(*idx)?.at(rest),
+      _ => None,
+    }
+  }
+
+  /// options of an object or instanced object, `None` for groups
+  pub fn component_mut(&mut self) -> Option<&mut ComponentOptions> {
+    match self {
+      TriadicaElement::Group(_) => None,
+      TriadicaElement::Object(component) => Some(component),
+      TriadicaElement::Instanced(options) => Some(&mut options.component),
+    }
+  }
+
+  /// bind a texture to a sampler uniform of this object, groups are left unchanged
+  pub fn with_texture(mut self, sampler: &str, texture: Texture) -> Self {
+    if let Some(component) = self.component_mut() {
+      component.textures.push((sampler.to_owned(), texture));
+    }
+    self
+  }
+
+  /// set default point size of this object, groups are left unchanged
+  pub fn with_point_size(mut self, point_size: f32) -> Self {
+    if let Some(component) = self.component_mut() {
+      component.point_size = point_size;
+    }
+    self
+  }
+
+  /// always draw this object instead of culling it by bounds of its positions,
+  /// for objects whose vertex shaders move them, like by a model matrix. groups are left unchanged
+  pub fn without_culling(mut self) -> Self {
+    if let Some(component) = self.component_mut() {
+      component.culling = false;
+    }
+    self
+  }
+
+  /// set render state of this object, groups are left unchanged
+  pub fn with_render_state(mut self, render_state: RenderState) -> Self {
+    if let Some(component) = self.component_mut() {
+      component.render_state = render_state;
     }
+    self
   }
 }
 
@@ -32,10 +104,24 @@ impl TriadicaElement {
 #[derive(Debug, Clone)]
 pub enum TriadicaElementTree {
   Group(Vec<TriadicaElementTree>),
-  Object(ComponentCache),
+  Object(Box<ComponentCache>),
 }
 
 impl TriadicaElementTree {
+  /// collect references of objects, without cloning vertex data
+  pub fn objects(&self) -> Vec<&ComponentCache> {
+    let mut result = Vec::new();
+    iter_collect_objects(self, &mut result);
+    result
+  }
+
+  /// objects with indices of children from the root down to them, paths are the same in the markup it compiled from
+  pub fn objects_with_paths(&self) -> Vec<(Vec<usize>, &ComponentCache)> {
+    let mut result = Vec::new();
+    iter_collect_paths(self, &mut Vec::new(), &mut result);
+    result
+  }
+
   /// TODO need iter for better performance, reduce cloning
   pub fn to_list(&self) -> Vec<ComponentCache> {
     let mut result: Vec<ComponentCache> = Vec::new();
@@ -45,12 +131,36 @@ impl TriadicaElementTree {
           result.extend_from_slice(&x.to_list())
         }
       }
-      TriadicaElementTree::Object(x) => result.push(x.to_owned()),
+      TriadicaElementTree::Object(x) => result.push((**x).to_owned()),
     }
     result
   }
 }
 
+fn iter_collect_objects<'a>(tree: &'a TriadicaElementTree, result: &mut Vec<&'a ComponentCache>) {
+  match tree {
+    TriadicaElementTree::Group(xs) => {
+      for x in xs {
+        iter_collect_objects(x, result);
+      }
+    }
+    TriadicaElementTree::Object(x) => result.push(x.as_ref()),
+  }
+}
+
+fn iter_collect_paths<'a>(tree: &'a TriadicaElementTree, path: &mut Vec<usize>, result: &mut Vec<(Vec<usize>, &'a ComponentCache)>) {
+  match tree {
+    TriadicaElementTree::Group(xs) => {
+      for (idx, x) in xs.iter().enumerate() {
+        path.push(idx);
+        iter_collect_paths(x, path, result);
+        path.pop();
+      }
+    }
+    TriadicaElementTree::Object(x) => result.push((path.to_owned(), x.as_ref())),
+  }
+}
+
 /// definition of user land component
 #[derive(Clone)]
 pub struct ComponentOptions {
@@ -59,7 +169,18 @@ pub struct ComponentOptions {
   pub fragment_shader: String,
   pub attr_names: Vec<(String, i8)>,
   pub packed_attrs: PackedAttrs,
-  pub get_uniforms: Rc<dyn Fn() -> VertexData>,
+  /// draw with `draw_elements` when given, indices refer to vertices of `packed_attrs`
+  pub indices: Option<Indices>,
+  /// textures by sampler uniform name, assigned to texture units in order at draw time
+  pub textures: Vec<(String, Texture)>,
+  /// blending, depth and culling while drawing this object
+  pub render_state: RenderState,
+  /// default size of points in pixels, bound to `u_point_size` for `DrawMode::Points`
+  pub point_size: f32,
+  /// skip drawing when bounds of `a_position` are out of view, turn it off for objects moved by their vertex shaders
+  pub culling: bool,
+  /// uniforms of this object, called every frame and bound after camera uniforms
+  pub get_uniforms: Rc<dyn Fn() -> UniformMap>,
 }
 
 impl Debug for ComponentOptions {
@@ -69,17 +190,151 @@ impl Debug for ComponentOptions {
 }
 
 impl ComponentOptions {
+  /// check attributes, layout of data and varyings against the shaders, without a GPU.
+  /// `compile_to_tree` calls it for every object
+  pub fn validate(&self) -> Result<(), TriadicaError> {
+    into_result(self.interface_problems(&[]))
+  }
+
+  /// problems of this component, `instance_attr_names` are also read by the vertex shader
+  fn interface_problems(&self, instance_attr_names: &[(String, i8)]) -> Vec<String> {
+    let vertex = ShaderDeclarations::parse(&self.vertex_shader, ShaderStage::Vertex);
+    let fragment = ShaderDeclarations::parse(&self.fragment_shader, ShaderStage::Fragment);
+    let attr_names: Vec<(String, i8)> = self.attr_names.iter().chain(instance_attr_names).cloned().collect();
+    let mut problems = check_attributes(&vertex, &attr_names);
+    problems.extend(check_stages(&vertex, &fragment));
+    problems.extend(self.packed_attrs.check_layout(&self.attr_names));
+    problems
+  }
+
   /// compile component into a webgl program that can be send to GPU
   pub fn compile_attributes(&self) -> ComponentCache {
-    ComponentCache {
+    let mut cache = ComponentCache {
+      id: ObjectId::next(),
       draw_mode: self.draw_mode,
       vertex_shader: self.vertex_shader.clone(),
       fragment_shader: self.fragment_shader.clone(),
+      program_key: ProgramKey::of(&self.vertex_shader, &self.fragment_shader),
       attr_names: self.attr_names.clone(),
       arrays: self.packed_attrs.flatten(),
       size: self.packed_attrs.len(),
+      indices: self.indices.clone(),
+      textures: self.textures.clone(),
+      render_state: self.render_state,
+      point_size: self.point_size,
+      instances: None,
+      bounds: None,
       get_uniforms: self.get_uniforms.clone(),
+    };
+    cache.bounds = self.bounds_of(&cache);
+    cache
+  }
+
+  /// bounds for culling, `None` when culling is off
+  fn bounds_of(&self, cache: &ComponentCache) -> Option<Bounds> {
+    if self.culling {
+      Bounds::of_object(cache)
+    } else {
+      None
+    }
+  }
+}
+
+/// a component drawn many times, per-instance attributes advance once per instance instead of per vertex
+#[derive(Debug, Clone)]
+pub struct InstancedOptions {
+  pub component: ComponentOptions,
+  pub instance_attr_names: Vec<(String, i8)>,
+  /// one item for each instance
+  pub instance_attrs: PackedAttrs,
+}
+
+impl InstancedOptions {
+  /// like `ComponentOptions::validate`, with per-instance attributes
+  pub fn validate(&self) -> Result<(), TriadicaError> {
+    let mut problems = self.component.interface_problems(&self.instance_attr_names);
+    problems.extend(self.instance_attrs.check_layout(&self.instance_attr_names));
+    into_result(problems)
+  }
+
+  pub fn compile_attributes(&self) -> Result<ComponentCache, TriadicaError> {
+    let instance_count = self.instance_attrs.len();
+    let instance_arrays = self.instance_attrs.flatten();
+    if instance_count > 0 && instance_arrays.len() != self.instance_attr_names.len() {
+      return Err(TriadicaError::AttributeMismatch(format!(
+        "instance attrs should have same length as instance_attr_names: {:?}",
+        self.instance_attr_names
+      )));
     }
+    let mut cache = ComponentCache {
+      instances: Some(InstanceArrays {
+        attr_names: self.instance_attr_names.clone(),
+        arrays: instance_arrays,
+        count: instance_count,
+      }),
+      ..self.component.compile_attributes()
+    };
+    cache.bounds = self.component.bounds_of(&cache);
+    Ok(cache)
+  }
+}
+
+/// compiled per-instance attributes
+#[derive(Debug, Clone)]
+pub struct InstanceArrays {
+  pub attr_names: Vec<(String, i8)>,
+  pub arrays: Vec<Vec<f32>>,
+  pub count: usize,
+}
+
+/// common per-instance attributes, shaders read them as
+/// `a_offset`(vec3), `a_scale`(float), `a_color`(vec4) and `a_rotation`(vec4 quaternion, `xyzw`).
+/// a vertex is scaled first, then rotated, then moved by the offset, `#include "triadica/instance"`
+/// (`INSTANCE_SNIPPET`) for `instance_transform` doing so in vertex shaders
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Instance {
+  pub offset: [f32; 3],
+  pub scale: f32,
+  pub color: [f32; 4],
+  pub rotation: [f32; 4],
+}
+
+impl Default for Instance {
+  fn default() -> Self {
+    Instance {
+      offset: [0.0; 3],
+      scale: 1.0,
+      color: [1.0; 4],
+      rotation: [0.0, 0.0, 0.0, 1.0],
+    }
+  }
+}
+
+impl Instance {
+  /// names and sizes matching `Instance::pack`
+  pub fn attr_names() -> Vec<(String, i8)> {
+    vec![
+      ("a_offset".to_owned(), 3),
+      ("a_scale".to_owned(), 1),
+      ("a_color".to_owned(), 4),
+      ("a_rotation".to_owned(), 4),
+    ]
+  }
+
+  pub fn pack(instances: &[Instance]) -> PackedAttrs {
+    PackedAttrs::List(
+      instances
+        .iter()
+        .map(|x| {
+          PackedAttrs::Item(vec![
+            VertexDataValue::Vec3(x.offset),
+            VertexDataValue::Float(x.scale),
+            VertexDataValue::Vec4(x.color),
+            VertexDataValue::Vec4(x.rotation),
+          ])
+        })
+        .collect(),
+    )
   }
 }
 
@@ -107,6 +362,33 @@ impl PackedAttrs {
     }
   }
 
+  /// problems of items not matching `attr_names` in number of values or numbers per value,
+  /// only the first item of each problem is reported
+  pub fn check_layout(&self, attr_names: &[(String, i8)]) -> Vec<String> {
+    let mut items = Vec::with_capacity(self.len());
+    iter_collect_items(self, &mut items);
+    let mut problems = Vec::new();
+    if let Some((idx, item)) = items.iter().enumerate().find(|(_, item)| item.len() != attr_names.len()) {
+      problems.push(format!(
+        "item {idx} has {} values, expected {} for {attr_names:?}",
+        item.len(),
+        attr_names.len()
+      ));
+    }
+    for (attr_idx, (name, size)) in attr_names.iter().enumerate() {
+      let mismatch = items.iter().enumerate().find_map(|(idx, item)| {
+        item
+          .get(attr_idx)
+          .filter(|value| value.len() != *size as usize)
+          .map(|value| (idx, value))
+      });
+      if let Some((idx, value)) = mismatch {
+        problems.push(format!("item {idx} has {} numbers for `{name}`, expected {size}", value.len()));
+      }
+    }
+    problems
+  }
+
   /// collect vertext with mutable data for performance
   pub fn flatten(&self) -> Vec<Vec<f32>> {
     let mut attrs = Vec::with_capacity(self.len());
@@ -138,6 +420,26 @@ impl PackedAttrs {
     }
   }
 
+  /// merge repeated vertices, returns a flat list of unique vertices and indices to rebuild the original order
+  pub fn to_indexed(&self) -> Result<(PackedAttrs, Indices), TriadicaError> {
+    let mut attrs = Vec::with_capacity(self.len());
+    iter_flatten_attributes(self, &mut attrs);
+
+    let mut unique: Vec<PackedAttrs> = Vec::new();
+    let mut seen: HashMap<Vec<u32>, u32> = HashMap::new();
+    let mut indices = Vec::with_capacity(attrs.len());
+    for vertex in attrs {
+      // compare by bits since f32 is not `Hash`
+      let key: Vec<u32> = vertex.iter().flat_map(|v| v.to_vec()).map(f32::to_bits).collect();
+      let idx = *seen.entry(key).or_insert_with(|| {
+        unique.push(PackedAttrs::Item(vertex));
+        (unique.len() - 1) as u32
+      });
+      indices.push(idx);
+    }
+    Ok((PackedAttrs::List(unique), Indices::from_u32(indices)?))
+  }
+
   /// get a sample of vertex data
   pub fn peek(&self) -> Option<VertexData> {
     match self {
@@ -153,6 +455,26 @@ impl PackedAttrs {
   }
 }
 
+fn iter_collect_items<'a>(packed_attrs: &'a PackedAttrs, items: &mut Vec<&'a VertexData>) {
+  match packed_attrs {
+    PackedAttrs::List(list) => {
+      for item in list {
+        iter_collect_items(item, items);
+      }
+    }
+    PackedAttrs::Item(item) => items.push(item),
+  }
+}
+
+/// `InterfaceMismatch` listing the problems, unless there are none
+fn into_result(problems: Vec<String>) -> Result<(), TriadicaError> {
+  if problems.is_empty() {
+    Ok(())
+  } else {
+    Err(TriadicaError::InterfaceMismatch(problems))
+  }
+}
+
 fn iter_flatten_attributes(packed_attrs: &PackedAttrs, attrs: &mut Vec<VertexData>) {
   match packed_attrs {
     PackedAttrs::List(list) => {
@@ -166,21 +488,93 @@ fn iter_flatten_attributes(packed_attrs: &PackedAttrs, attrs: &mut Vec<VertexDat
   }
 }
 
+/// identifies a compiled object, GPU resources are uploaded and disposed by it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObjectId(u64);
+
+static OBJECT_ID_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+impl ObjectId {
+  /// every compilation gets a new id
+  pub fn next() -> Self {
+    ObjectId(OBJECT_ID_COUNTER.fetch_add(1, Ordering::Relaxed))
+  }
+}
+
 /// cached struct for compiled shaders
 #[derive(Clone)]
 pub struct ComponentCache {
+  pub id: ObjectId,
   pub draw_mode: DrawMode,
   pub vertex_shader: String,
   pub fragment_shader: String,
+  /// identifies the program of the shaders above, without hashing them on every lookup
+  pub program_key: ProgramKey,
   pub attr_names: Vec<(String, i8)>,
-  /// TODO need buffers
+  /// kept after uploading, for re-uploading and CPU rendering
   pub arrays: Vec<Vec<f32>>,
+  /// number of vertices
   pub size: usize,
-  pub get_uniforms: Rc<dyn Fn() -> VertexData>,
+  pub indices: Option<Indices>,
+  pub textures: Vec<(String, Texture)>,
+  pub render_state: RenderState,
+  pub point_size: f32,
+  /// `Some` for instanced objects, drawn with `draw_*_instanced`
+  pub instances: Option<InstanceArrays>,
+  /// for skipping the object when out of view, `None` is always drawn, see `ComponentOptions::culling`
+  pub bounds: Option<Bounds>,
+  pub get_uniforms: Rc<dyn Fn() -> UniformMap>,
+}
+
+impl ComponentCache {
+  /// nothing to draw, like objects from empty lists or with no instances. renderers skip them
+  pub fn is_empty(&self) -> bool {
+    self.size == 0 || self.arrays.is_empty() || self.instances.as_ref().is_some_and(|instances| instances.count == 0)
+  }
 }
 
 impl Debug for ComponentCache {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    f.write_fmt(format_args!("TODO ComponentCache: {:?}", self.draw_mode))
+    f.write_fmt(format_args!("TODO ComponentCache: {:?} {:?}", self.id, self.draw_mode))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn vertex(x: f32, y: f32) -> PackedAttrs {
+    PackedAttrs::Item(vec![VertexDataValue::Vec2([x, y]), VertexDataValue::Float(1.0)])
+  }
+
+  #[test]
+  fn merges_repeated_vertices_into_indices() {
+    // two triangles of a quad, sharing an edge
+    let quad = PackedAttrs::List(vec![
+      PackedAttrs::List(vec![vertex(0.0, 0.0), vertex(1.0, 0.0), vertex(1.0, 1.0)]),
+      PackedAttrs::List(vec![vertex(0.0, 0.0), vertex(1.0, 1.0), vertex(0.0, 1.0)]),
+    ]);
+    let (unique, indices) = quad.to_indexed().unwrap();
+    assert_eq!(unique.len(), 4);
+    assert_eq!(indices, Indices::U16(vec![0, 1, 2, 0, 2, 3]));
+
+    // indices rebuild the original order, positions are the first array with 2 floats per vertex
+    let original = &quad.flatten()[0];
+    let merged = &unique.flatten()[0];
+    for idx in 0..indices.len() {
+      let at = indices.get(idx).unwrap();
+      assert_eq!(original[idx * 2..idx * 2 + 2], merged[at * 2..at * 2 + 2]);
+    }
+  }
+
+  #[test]
+  fn keeps_vertices_differing_in_any_attribute() {
+    let a = PackedAttrs::Item(vec![VertexDataValue::Float(0.0), VertexDataValue::Float(1.0)]);
+    let b = PackedAttrs::Item(vec![VertexDataValue::Float(0.0), VertexDataValue::Float(2.0)]);
+    // `-0.0 == 0.0`, but bits differ
+    let c = PackedAttrs::Item(vec![VertexDataValue::Float(-0.0), VertexDataValue::Float(1.0)]);
+    let (unique, indices) = PackedAttrs::List(vec![a.clone(), b, a, c]).to_indexed().unwrap();
+    assert_eq!(unique.len(), 3);
+    assert_eq!(indices, Indices::U16(vec![0, 1, 0, 2]));
   }
 }
@@ -0,0 +1,89 @@
+//! listens to loss and restoration of the WebGL context of a canvas, so rendering pauses and resources are uploaded again
+
+use std::{cell::RefCell, rc::Rc};
+
+use wasm_bindgen::{prelude::*, JsCast};
+use web_sys::{Event, HtmlCanvasElement};
+
+use crate::component::TriadicaElementTree;
+use crate::error::TriadicaError;
+use crate::renderer::Renderer;
+use crate::viewer;
+use crate::webgl::WebGlBackend;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextEvent {
+  /// `webglcontextlost`, every GPU resource is gone
+  Lost,
+  /// `webglcontextrestored`, the context is usable again but empty
+  Restored,
+}
+
+/// collects context events of a canvas until the frame loop handles them.
+/// listeners are removed when dropped, so keep it as long as the canvas is rendered
+pub struct ContextWatcher {
+  canvas: HtmlCanvasElement,
+  events: Rc<RefCell<Vec<ContextEvent>>>,
+  on_lost: Closure<dyn FnMut(Event)>,
+  on_restored: Closure<dyn FnMut(Event)>,
+}
+
+impl ContextWatcher {
+  pub fn new(canvas: &HtmlCanvasElement) -> Result<Self, JsValue> {
+    let events = Rc::new(RefCell::new(Vec::new()));
+
+    let lost_events = events.clone();
+    let on_lost = Closure::wrap(Box::new(move |event: Event| {
+      // the browser only restores contexts when the default action is prevented
+      event.prevent_default();
+      lost_events.borrow_mut().push(ContextEvent::Lost);
+    }) as Box<dyn FnMut(Event)>);
+
+    let restored_events = events.clone();
+    let on_restored = Closure::wrap(Box::new(move |_event: Event| {
+      restored_events.borrow_mut().push(ContextEvent::Restored);
+    }) as Box<dyn FnMut(Event)>);
+
+    canvas.add_event_listener_with_callback("webglcontextlost", on_lost.as_ref().unchecked_ref())?;
+    canvas.add_event_listener_with_callback("webglcontextrestored", on_restored.as_ref().unchecked_ref())?;
+
+    Ok(Self {
+      canvas: canvas.to_owned(),
+      events,
+      on_lost,
+      on_restored,
+    })
+  }
+
+  /// events since last call, in order
+  pub fn take_events(&self) -> Vec<ContextEvent> {
+    self.events.take()
+  }
+
+  /// handle events in the frame loop before painting, `tree` is uploaded again after restoring.
+  /// painting is skipped by the renderer while the context is lost
+  pub fn apply(&self, renderer: &mut Renderer<WebGlBackend>, tree: &TriadicaElementTree) -> Result<(), TriadicaError> {
+    for event in self.take_events() {
+      match event {
+        ContextEvent::Lost => renderer.context_lost(),
+        ContextEvent::Restored => {
+          crate::context_setup(&renderer.backend.context);
+          renderer.context_restored(tree)?;
+          viewer::mark_dirty();
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+impl Drop for ContextWatcher {
+  fn drop(&mut self) {
+    let _ = self
+      .canvas
+      .remove_event_listener_with_callback("webglcontextlost", self.on_lost.as_ref().unchecked_ref());
+    let _ = self
+      .canvas
+      .remove_event_listener_with_callback("webglcontextrestored", self.on_restored.as_ref().unchecked_ref());
+  }
+}
@@ -0,0 +1,213 @@
+//! GLSL ES dialects, and up-conversion of 1.00 shaders to 3.00 so both can be linked together
+
+use std::borrow::Cow;
+
+use crate::error::ShaderStage;
+use crate::preprocess::ORIGIN_MARKER;
+
+/// fragment output declared by up-converted fragment shaders, replacing `gl_FragColor`
+pub const FRAG_COLOR_OUTPUT: &str = "triadica_FragColor";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlslVersion {
+  /// WebGL1 dialect, with `attribute`, `varying` and `gl_FragColor`
+  Es100,
+  /// `#version 300 es`, with `in`, `out`, `flat`, integer attributes and `texelFetch`
+  Es300,
+}
+
+impl GlslVersion {
+  /// read from `#version` on first line, 1.00 when missing
+  pub fn of(source: &str) -> Self {
+    match source.lines().next().map(str::trim) {
+      Some(line) if line.starts_with("#version") && line.contains("300") => GlslVersion::Es300,
+      _ => GlslVersion::Es100,
+    }
+  }
+}
+
+/// when `Renderer` converts 1.00 shaders to 3.00 before linking
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ShaderUpgrade {
+  /// link sources as they are
+  Never,
+  /// convert the 1.00 shader when paired with a 3.00 one, which can not be linked otherwise
+  #[default]
+  Mixed,
+  /// convert every 1.00 shader
+  Always,
+}
+
+impl ShaderUpgrade {
+  /// sources to link, borrowed when unchanged
+  pub fn apply<'a>(&self, vertex_shader: &'a str, fragment_shader: &'a str) -> (Cow<'a, str>, Cow<'a, str>) {
+    let upgrade = match self {
+      ShaderUpgrade::Never => false,
+      ShaderUpgrade::Mixed => GlslVersion::of(vertex_shader) != GlslVersion::of(fragment_shader),
+      ShaderUpgrade::Always => true,
+    };
+    if upgrade {
+      (
+        upgrade_shader(vertex_shader, ShaderStage::Vertex),
+        upgrade_shader(fragment_shader, ShaderStage::Fragment),
+      )
+    } else {
+      (Cow::Borrowed(vertex_shader), Cow::Borrowed(fragment_shader))
+    }
+  }
+}
+
+/// extensions of WebGL1 that are core in 3.00, their directives are dropped
+const CORE_EXTENSIONS: [&str; 4] = [
+  "GL_OES_standard_derivatives",
+  "GL_EXT_frag_depth",
+  "GL_EXT_shader_texture_lod",
+  "GL_EXT_draw_buffers",
+];
+
+/// convert a 1.00 shader to 3.00, sources already in 3.00 are returned unchanged.
+/// keywords and built-ins are renamed, `gl_FragColor` turns into `FRAG_COLOR_OUTPUT`.
+/// line numbers are kept, so errors still point to lines of the original source
+pub fn upgrade_shader(source: &str, stage: ShaderStage) -> Cow<'_, str> {
+  if GlslVersion::of(source) == GlslVersion::Es300 {
+    return Cow::Borrowed(source);
+  }
+  let renames: &[(&str, &str)] = match stage {
+    ShaderStage::Vertex => &[
+      ("attribute", "in"),
+      ("varying", "out"),
+      ("texture2D", "texture"),
+      ("textureCube", "texture"),
+      ("texture2DLod", "textureLod"),
+    ],
+    ShaderStage::Fragment => &[
+      ("varying", "in"),
+      ("texture2D", "texture"),
+      ("textureCube", "texture"),
+      ("texture2DLodEXT", "textureLod"),
+      ("gl_FragColor", FRAG_COLOR_OUTPUT),
+      ("gl_FragDepthEXT", "gl_FragDepth"),
+    ],
+  };
+
+  let mut lines: Vec<String> = Vec::new();
+  for (idx, line) in source.lines().enumerate() {
+    let trimmed = line.trim_start();
+    let dropped = (idx == 0 && trimmed.starts_with("#version"))
+      || (trimmed.starts_with("#extension") && CORE_EXTENSIONS.iter().any(|x| trimmed.contains(x)));
+    if dropped {
+      // keep the line, so later lines keep their numbers
+      lines.push(String::new());
+    } else {
+      lines.push(rename_words(&line.replace("gl_FragData[0]", "gl_FragColor"), renames));
+    }
+  }
+
+  let writes_color = lines.iter().any(|line| line.contains(FRAG_COLOR_OUTPUT));
+  if stage == ShaderStage::Fragment && writes_color {
+    // prepended to first line of code instead of a new line, `#extension`s should stay before it
+    let code = lines.iter().position(|line| {
+      let line = line.trim_start();
+      !line.is_empty() && !line.starts_with('#') && !line.starts_with("//")
+    });
+    if let Some(idx) = code {
+      lines[idx] = format!("out highp vec4 {FRAG_COLOR_OUTPUT}; {}", lines[idx]);
+    }
+  }
+
+  let mut upgraded = String::from("#version 300 es\n");
+  if !source.contains(ORIGIN_MARKER) {
+    // the version line shifts lines, name them so errors are mapped back
+    upgraded.push_str(&format!("{ORIGIN_MARKER}{stage}:1\n"));
+  }
+  for line in lines {
+    upgraded.push_str(&line);
+    upgraded.push('\n');
+  }
+  Cow::Owned(upgraded)
+}
+
+/// replace whole identifiers, leaving longer identifiers containing them
+fn rename_words(line: &str, renames: &[(&str, &str)]) -> String {
+  let mut result = String::with_capacity(line.len());
+  let mut word = String::new();
+  let flush = |word: &mut String, result: &mut String| {
+    let renamed = renames.iter().find(|(from, _)| from == word).map_or(word.as_str(), |(_, to)| to);
+    result.push_str(renamed);
+    word.clear();
+  };
+  for c in line.chars() {
+    if c.is_ascii_alphanumeric() || c == '_' {
+      word.push(c);
+    } else {
+      flush(&mut word, &mut result);
+      result.push(c);
+    }
+  }
+  flush(&mut word, &mut result);
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::preprocess::origin_of;
+
+  const VERTEX: &str = "attribute vec3 a_position;
+varying vec2 v_uv;
+void main() {
+  v_uv = a_position.xy;
+  gl_Position = vec4(a_position, 1.0);
+}
+";
+
+  const FRAGMENT: &str = "#extension GL_OES_standard_derivatives : enable
+precision mediump float;
+varying vec2 v_uv;
+uniform sampler2D u_texture;
+void main() {
+  gl_FragColor = texture2D(u_texture, v_uv) + vec4(fwidth(v_uv.x));
+}
+";
+
+  #[test]
+  fn upgrades_vertex_keywords() {
+    let upgraded = upgrade_shader(VERTEX, ShaderStage::Vertex);
+    assert_eq!(GlslVersion::of(&upgraded), GlslVersion::Es300);
+    assert!(upgraded.contains("in vec3 a_position;"));
+    assert!(upgraded.contains("out vec2 v_uv;"));
+    // identifiers containing keywords are kept
+    assert!(upgraded.contains("gl_Position = vec4(a_position, 1.0);"));
+  }
+
+  #[test]
+  fn upgrades_fragment_output_and_extensions() {
+    let upgraded = upgrade_shader(FRAGMENT, ShaderStage::Fragment);
+    assert!(!upgraded.contains("#extension"));
+    assert!(!upgraded.contains("gl_FragColor"));
+    assert!(upgraded.contains(&format!("out highp vec4 {FRAG_COLOR_OUTPUT}; precision mediump float;")));
+    assert!(upgraded.contains(&format!("{FRAG_COLOR_OUTPUT} = texture(u_texture, v_uv)")));
+    assert!(upgraded.contains("in vec2 v_uv;"));
+  }
+
+  #[test]
+  fn keeps_lines_of_original_source() {
+    let upgraded = upgrade_shader(FRAGMENT, ShaderStage::Fragment);
+    let line = upgraded.lines().position(|line| line.contains("texture(")).unwrap() + 1;
+    assert_eq!(origin_of(&upgraded, line), Some(("fragment".to_owned(), 6)));
+  }
+
+  #[test]
+  fn leaves_es300_sources_and_unmixed_pairs() {
+    let source = "#version 300 es\nin vec3 a_position;\nvoid main() {}\n";
+    assert!(matches!(upgrade_shader(source, ShaderStage::Vertex), Cow::Borrowed(_)));
+
+    let (vertex, fragment) = ShaderUpgrade::Mixed.apply(VERTEX, FRAGMENT);
+    assert!(matches!((vertex, fragment), (Cow::Borrowed(_), Cow::Borrowed(_))));
+    let (vertex, fragment) = ShaderUpgrade::Mixed.apply(source, FRAGMENT);
+    assert!(matches!(vertex, Cow::Borrowed(_)));
+    assert_eq!(GlslVersion::of(&fragment), GlslVersion::Es300);
+    let (vertex, _) = ShaderUpgrade::Never.apply(source, FRAGMENT);
+    assert_eq!(vertex, source);
+  }
+}
@@ -0,0 +1,371 @@
+//! errors of building and drawing objects, shader errors carry diagnostics pointing into sources
+
+use std::fmt::{self, Display};
+
+use wasm_bindgen::JsValue;
+use web_sys::WebGl2RenderingContext;
+
+use crate::preprocess::origin_of;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderStage {
+  Vertex,
+  Fragment,
+}
+
+impl From<ShaderStage> for u32 {
+  fn from(x: ShaderStage) -> Self {
+    match x {
+      ShaderStage::Vertex => WebGl2RenderingContext::VERTEX_SHADER,
+      ShaderStage::Fragment => WebGl2RenderingContext::FRAGMENT_SHADER,
+    }
+  }
+}
+
+impl Display for ShaderStage {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ShaderStage::Vertex => f.write_str("vertex"),
+      ShaderStage::Fragment => f.write_str("fragment"),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+  Error,
+  Warning,
+}
+
+/// one message of a shader info log, lines count from 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderDiagnostic {
+  pub severity: Severity,
+  pub line: Option<usize>,
+  pub column: Option<usize>,
+  pub message: String,
+  /// file and line before `#include`s were expanded, see `ShaderLibrary`
+  pub origin: Option<(String, usize)>,
+}
+
+impl ShaderDiagnostic {
+  /// parse an info log, understands `ERROR: 0:12: message` from ANGLE and browsers,
+  /// and `0:12(5): error: message` from Mesa. unknown lines are kept as messages without location
+  pub fn parse_log(log: &str) -> Vec<Self> {
+    log
+      .lines()
+      .map(str::trim)
+      .filter(|line| !line.is_empty() && !line.starts_with('\0'))
+      .map(|line| Self::parse_line(line).unwrap_or_else(|| Self::unlocated(line)))
+      .collect()
+  }
+
+  fn unlocated(line: &str) -> Self {
+    ShaderDiagnostic {
+      severity: Severity::Error,
+      line: None,
+      column: None,
+      message: line.to_owned(),
+      origin: None,
+    }
+  }
+
+  fn parse_line(line: &str) -> Option<Self> {
+    // ANGLE style, `ERROR: 0:12: 'foo' : undeclared identifier`
+    for (prefix, severity) in [("ERROR:", Severity::Error), ("WARNING:", Severity::Warning)] {
+      if let Some(rest) = line.strip_prefix(prefix) {
+        let mut parts = rest.trim_start().splitn(3, ':');
+        let _source = parts.next()?.trim().parse::<usize>().ok()?;
+        let line = parts.next()?.trim().parse::<usize>().ok()?;
+        return Some(ShaderDiagnostic {
+          severity,
+          line: Some(line),
+          column: None,
+          message: parts.next()?.trim().to_owned(),
+          origin: None,
+        });
+      }
+    }
+
+    // Mesa style, `0:12(5): error: 'foo' undeclared`
+    let (location, rest) = line.split_once(": ")?;
+    let (_source, position) = location.split_once(':')?;
+    let (line_no, column) = position.trim_end_matches(')').split_once('(')?;
+    let (severity, message) = rest.split_once(": ")?;
+    let severity = match severity {
+      "error" => Severity::Error,
+      "warning" => Severity::Warning,
+      _ => return None,
+    };
+    Some(ShaderDiagnostic {
+      severity,
+      line: Some(line_no.parse().ok()?),
+      column: Some(column.parse().ok()?),
+      message: message.to_owned(),
+      origin: None,
+    })
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TriadicaError {
+  /// info log parsed into diagnostics, `source` is kept for printing excerpts
+  ShaderCompile {
+    stage: ShaderStage,
+    log: String,
+    diagnostics: Vec<ShaderDiagnostic>,
+    source: String,
+  },
+  ProgramLink {
+    log: String,
+  },
+  /// attributes not matching between data and shaders
+  AttributeMismatch(String),
+  /// declarations of shaders not matching data of an object, or each other, found before uploading
+  InterfaceMismatch(Vec<String>),
+  BufferCreation(String),
+  /// bad `#include` when expanding a shader, at line of the file containing it
+  Preprocess {
+    file: String,
+    line: usize,
+    message: String,
+  },
+  ContextLost,
+  /// images of the software renderer failing to encode, decode, read or write, or to compare
+  Image(String),
+  /// invalid uniforms, textures, indices or render targets
+  Other(String),
+}
+
+impl TriadicaError {
+  pub fn shader_compile(stage: ShaderStage, log: String, source: &str) -> Self {
+    let mut diagnostics = ShaderDiagnostic::parse_log(&log);
+    for diagnostic in &mut diagnostics {
+      diagnostic.origin = diagnostic.line.and_then(|line| origin_of(source, line));
+    }
+    TriadicaError::ShaderCompile {
+      stage,
+      diagnostics,
+      log,
+      source: source.to_owned(),
+    }
+  }
+}
+
+/// creating objects fails with `null` after context is lost, tell it apart from other failures
+pub(crate) fn lost_or(context: &WebGl2RenderingContext, error: TriadicaError) -> TriadicaError {
+  if context.is_context_lost() {
+    TriadicaError::ContextLost
+  } else {
+    error
+  }
+}
+
+/// lines of context printed around each diagnostic
+const EXCERPT_RADIUS: usize = 2;
+
+impl Display for TriadicaError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      TriadicaError::ShaderCompile {
+        stage,
+        log,
+        diagnostics,
+        source,
+      } => {
+        writeln!(f, "failed to compile {stage} shader")?;
+        if diagnostics.is_empty() {
+          return write!(f, "{log}");
+        }
+        let lines: Vec<&str> = source.lines().collect();
+        let width = lines.len().to_string().len();
+        for diagnostic in diagnostics {
+          let label = match diagnostic.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+          };
+          let origin = match &diagnostic.origin {
+            Some((file, line)) => format!(" ({file}:{line})"),
+            None => String::new(),
+          };
+          match (diagnostic.line, diagnostic.column) {
+            (Some(line), Some(column)) => writeln!(f, "{label} at {line}:{column}{origin}: {}", diagnostic.message)?,
+            (Some(line), None) => writeln!(f, "{label} at line {line}{origin}: {}", diagnostic.message)?,
+            _ => writeln!(f, "{label}: {}", diagnostic.message)?,
+          }
+          let line = match diagnostic.line {
+            Some(line) if line >= 1 && line <= lines.len() => line,
+            _ => continue,
+          };
+          for idx in line.saturating_sub(EXCERPT_RADIUS).max(1)..=(line + EXCERPT_RADIUS).min(lines.len()) {
+            let marker = if idx == line { '>' } else { ' ' };
+            writeln!(f, "{marker} {idx:>width$} | {}", lines[idx - 1])?;
+            if let (true, Some(column)) = (idx == line, diagnostic.column) {
+              writeln!(f, "  {:>width$} | {:>column$}", "", "^")?;
+            }
+          }
+        }
+        Ok(())
+      }
+      TriadicaError::ProgramLink { log } => write!(f, "failed to link program: {log}"),
+      TriadicaError::AttributeMismatch(message) => write!(f, "attribute mismatch: {message}"),
+      TriadicaError::InterfaceMismatch(problems) => {
+        write!(f, "object does not match its shaders")?;
+        for problem in problems {
+          write!(f, "\n- {problem}")?;
+        }
+        Ok(())
+      }
+      TriadicaError::BufferCreation(message) => write!(f, "failed to create buffer: {message}"),
+      TriadicaError::Preprocess { file, line, message } => write!(f, "{file}:{line}: {message}"),
+      TriadicaError::ContextLost => f.write_str("WebGL context is lost"),
+      TriadicaError::Image(message) => write!(f, "image: {message}"),
+      TriadicaError::Other(message) => f.write_str(message),
+    }
+  }
+}
+
+impl std::error::Error for TriadicaError {}
+
+/// for errors raised by value helpers, like `UniformValue::flatten`
+impl From<String> for TriadicaError {
+  fn from(message: String) -> Self {
+    TriadicaError::Other(message)
+  }
+}
+
+impl From<TriadicaError> for JsValue {
+  fn from(error: TriadicaError) -> Self {
+    JsValue::from_str(&error.to_string())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::preprocess::ShaderLibrary;
+
+  /// log of Chrome and other ANGLE based browsers, ending with a NUL
+  const ANGLE_LOG: &str = "ERROR: 0:4: 'colr' : undeclared identifier\nERROR: 0:4: 'assign' :  cannot convert from 'const mediump float' to 'out highp 4-component vector of float'\nWARNING: 0:2: 'GL_OES_standard_derivatives' : extension is not supported\nERROR: 3 compilation errors.  No code generated.\n\n\0";
+
+  /// log of Firefox on Linux with Mesa drivers
+  const MESA_LOG: &str = "0:4(3): error: `colr' undeclared\n0:4(3): error: value of type float cannot be assigned to variable of type vec4\n0:2(12): warning: extension `GL_OES_standard_derivatives' unsupported in fragment shader\n";
+
+  const SOURCE: &str = "#version 300 es\nprecision mediump float;\nout vec4 color;\n  colr = 1.0;\nvoid main() {}\n";
+
+  #[test]
+  fn parses_angle_logs() {
+    let diagnostics = ShaderDiagnostic::parse_log(ANGLE_LOG);
+    assert_eq!(diagnostics.len(), 4);
+    assert_eq!(
+      diagnostics[0],
+      ShaderDiagnostic {
+        severity: Severity::Error,
+        line: Some(4),
+        column: None,
+        message: "'colr' : undeclared identifier".to_owned(),
+        origin: None,
+      }
+    );
+    assert!(diagnostics[1].message.starts_with("'assign' :  cannot convert"));
+    assert_eq!((diagnostics[2].severity, diagnostics[2].line), (Severity::Warning, Some(2)));
+    // summary has no location
+    assert_eq!(
+      (diagnostics[3].line, diagnostics[3].message.as_str()),
+      (None, "ERROR: 3 compilation errors.  No code generated.")
+    );
+  }
+
+  #[test]
+  fn parses_mesa_logs() {
+    let diagnostics = ShaderDiagnostic::parse_log(MESA_LOG);
+    assert_eq!(diagnostics.len(), 3);
+    assert_eq!(
+      diagnostics[0],
+      ShaderDiagnostic {
+        severity: Severity::Error,
+        line: Some(4),
+        column: Some(3),
+        message: "`colr' undeclared".to_owned(),
+        origin: None,
+      }
+    );
+    assert_eq!(
+      (diagnostics[2].severity, diagnostics[2].line, diagnostics[2].column),
+      (Severity::Warning, Some(2), Some(12))
+    );
+  }
+
+  #[test]
+  fn maps_lines_back_to_included_files() {
+    let mut library = ShaderLibrary::empty();
+    library.register("lib/color", "vec4 tint() {\n  return colr;\n}");
+    let source = library
+      .expand(
+        "main.frag",
+        "#version 300 es\nprecision mediump float;\n#include \"lib/color\"\nvoid main() {\n  fail;\n}",
+      )
+      .unwrap();
+    let line_of = |text: &str| source.lines().position(|line| line.contains(text)).unwrap() + 1;
+    let log = format!(
+      "ERROR: 0:{}: 'colr' : undeclared identifier\nERROR: 0:{}: 'fail' : undeclared identifier\n",
+      line_of("return colr"),
+      line_of("fail;")
+    );
+    let error = TriadicaError::shader_compile(ShaderStage::Fragment, log, &source);
+    match &error {
+      TriadicaError::ShaderCompile { diagnostics, .. } => {
+        assert_eq!(diagnostics[0].origin, Some(("lib/color".to_owned(), 2)));
+        assert_eq!(diagnostics[1].origin, Some(("main.frag".to_owned(), 5)));
+      }
+      error => panic!("unexpected {error:?}"),
+    }
+    let text = error.to_string();
+    let expected = format!(
+      "error at line {} (lib/color:2): 'colr' : undeclared identifier",
+      line_of("return colr")
+    );
+    assert!(text.contains(&expected), "{text}");
+    assert!(
+      text
+        .lines()
+        .any(|line| line.starts_with('>') && line.ends_with(" |   return colr;")),
+      "{text}"
+    );
+  }
+
+  #[test]
+  fn prints_excerpts_around_lines() {
+    let error = TriadicaError::shader_compile(ShaderStage::Fragment, MESA_LOG.lines().next().unwrap().to_owned(), SOURCE);
+    assert_eq!(
+      error.to_string(),
+      "failed to compile fragment shader
+error at 4:3: `colr' undeclared
+  2 | precision mediump float;
+  3 | out vec4 color;
+> 4 |   colr = 1.0;
+    |   ^
+  5 | void main() {}
+"
+    );
+
+    let error = TriadicaError::shader_compile(ShaderStage::Vertex, "ERROR: 0:1: 'x' : bad\nlinker failed".to_owned(), SOURCE);
+    assert_eq!(
+      error.to_string(),
+      "failed to compile vertex shader
+error at line 1: 'x' : bad
+> 1 | #version 300 es
+  2 | precision mediump float;
+  3 | out vec4 color;
+error: linker failed
+"
+    );
+  }
+
+  #[test]
+  fn keeps_logs_without_diagnostics() {
+    let error = TriadicaError::shader_compile(ShaderStage::Vertex, String::new(), SOURCE);
+    assert_eq!(error.to_string(), "failed to compile vertex shader\n");
+    assert!(ShaderDiagnostic::parse_log("\0").is_empty());
+  }
+}
@@ -0,0 +1,379 @@
+//! reads global declarations out of GLSL sources, so objects can be checked against their shaders without a GPU
+
+use std::collections::HashSet;
+
+use crate::error::ShaderStage;
+
+/// an attribute, uniform or varying declared at global scope
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Declaration {
+  pub name: String,
+  /// type without precision, like `vec3`
+  pub type_name: String,
+  /// length of arrays, `None` for single values
+  pub array_len: Option<usize>,
+  /// line in source, counting from 1
+  pub line: usize,
+}
+
+impl Declaration {
+  /// numbers per vertex of scalar and vector types, `None` for matrices, structs and samplers
+  pub fn components(&self) -> Option<i8> {
+    match self.type_name.as_str() {
+      "float" | "int" | "uint" | "bool" => Some(1),
+      "vec2" | "ivec2" | "uvec2" | "bvec2" => Some(2),
+      "vec3" | "ivec3" | "uvec3" | "bvec3" => Some(3),
+      "vec4" | "ivec4" | "uvec4" | "bvec4" => Some(4),
+      _ => None,
+    }
+  }
+}
+
+/// declarations of one shader, for GLSL ES 1.00 and 3.00
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShaderDeclarations {
+  /// `attribute` or `in` of vertex shaders
+  pub attributes: Vec<Declaration>,
+  pub uniforms: Vec<Declaration>,
+  /// `varying` or `out` of vertex shaders, `varying` or `in` of fragment shaders
+  pub varyings: Vec<Declaration>,
+}
+
+/// words before the type in declarations, besides storage qualifiers
+const QUALIFIERS: [&str; 8] = ["highp", "mediump", "lowp", "flat", "smooth", "centroid", "invariant", "const"];
+
+impl ShaderDeclarations {
+  /// read declarations at global scope. `#ifdef` and `#ifndef` follow `#define`s of the source,
+  /// other conditions are taken as true
+  pub fn parse(source: &str, stage: ShaderStage) -> Self {
+    let mut declarations = Self::default();
+    let mut statement = String::new();
+    let mut statement_line = 0;
+    let mut depth = 0usize;
+    let mut defines: HashSet<String> = HashSet::new();
+    // whether each nested branch is taken, and whether any branch of it was taken
+    let mut branches: Vec<(bool, bool)> = Vec::new();
+
+    for (idx, line) in strip_comments(source).lines().enumerate() {
+      let active = branches.iter().all(|(taken, _)| *taken);
+      let trimmed = line.trim();
+      if let Some(directive) = trimmed.strip_prefix('#') {
+        let mut words = directive.split_whitespace();
+        let keyword = words.next().unwrap_or("");
+        let name = words.next().unwrap_or("");
+        match keyword {
+          "define" if active => {
+            defines.insert(name.to_owned());
+          }
+          "undef" if active => {
+            defines.remove(name);
+          }
+          "ifdef" | "ifndef" | "if" => {
+            let taken = match keyword {
+              "ifdef" => defines.contains(name),
+              "ifndef" => !defines.contains(name),
+              _ => true,
+            };
+            branches.push((taken, taken));
+          }
+          "elif" | "else" => {
+            if let Some((taken, any)) = branches.last_mut() {
+              *taken = !*any;
+              *any = true;
+            }
+          }
+          "endif" => {
+            branches.pop();
+          }
+          _ => {}
+        }
+        continue;
+      }
+      if !active {
+        continue;
+      }
+
+      for c in line.chars().chain(Some('\n')) {
+        match c {
+          '{' => depth += 1,
+          '}' => {
+            depth = depth.saturating_sub(1);
+            // bodies of functions, structs and blocks, nothing before them is an interface declaration
+            if depth == 0 {
+              statement.clear();
+            }
+          }
+          ';' if depth == 0 => {
+            declarations.add(&statement, statement_line, stage);
+            statement.clear();
+          }
+          _ if depth == 0 => {
+            if statement.trim().is_empty() && !c.is_whitespace() {
+              statement_line = idx + 1;
+            }
+            statement.push(c);
+          }
+          _ => {}
+        }
+      }
+    }
+    declarations
+  }
+
+  fn add(&mut self, statement: &str, line: usize, stage: ShaderStage) {
+    let statement = strip_layout(statement);
+    // function prototypes, and initializers which interface variables do not have
+    if statement.contains('(') || statement.contains('=') {
+      return;
+    }
+    let mut declarators = statement.split(',');
+    let head: Vec<&str> = declarators.next().unwrap_or("").split_whitespace().collect();
+    let storage = match head.iter().find(|word| !QUALIFIERS.contains(word)) {
+      Some(word) => *word,
+      None => return,
+    };
+    let list = match (storage, stage) {
+      ("uniform", _) => &mut self.uniforms,
+      ("attribute", ShaderStage::Vertex) | ("in", ShaderStage::Vertex) => &mut self.attributes,
+      ("varying", _) | ("out", ShaderStage::Vertex) | ("in", ShaderStage::Fragment) => &mut self.varyings,
+      _ => return,
+    };
+    // `type name`, possibly with `[n]` after either
+    let (type_name, first) = match head.as_slice() {
+      [.., type_name, name] if *type_name != storage => (*type_name, *name),
+      _ => return,
+    };
+    let (type_name, type_len) = split_array(type_name);
+    for declarator in Some(first).into_iter().chain(declarators.map(str::trim)) {
+      let (name, len) = split_array(&declarator.replace(' ', ""));
+      list.push(Declaration {
+        name,
+        type_name: type_name.to_owned(),
+        array_len: len.or(type_len),
+        line,
+      });
+    }
+  }
+
+  pub fn attribute(&self, name: &str) -> Option<&Declaration> {
+    self.attributes.iter().find(|x| x.name == name)
+  }
+
+  pub fn uniform(&self, name: &str) -> Option<&Declaration> {
+    self.uniforms.iter().find(|x| x.name == name)
+  }
+
+  pub fn varying(&self, name: &str) -> Option<&Declaration> {
+    self.varyings.iter().find(|x| x.name == name)
+  }
+}
+
+/// problems between a vertex and a fragment shader, like a varying read but not written
+pub fn check_stages(vertex: &ShaderDeclarations, fragment: &ShaderDeclarations) -> Vec<String> {
+  let mut problems = Vec::new();
+  for input in &fragment.varyings {
+    match vertex.varying(&input.name) {
+      None => problems.push(format!(
+        "varying `{}` read by fragment shader at line {} is not written by vertex shader",
+        input.name, input.line
+      )),
+      Some(output) if (&output.type_name, output.array_len) != (&input.type_name, input.array_len) => problems.push(format!(
+        "varying `{}` is `{}` in vertex shader at line {}, but `{}` in fragment shader at line {}",
+        input.name,
+        type_label(output),
+        output.line,
+        type_label(input),
+        input.line
+      )),
+      Some(_) => {}
+    }
+  }
+  for uniform in &fragment.uniforms {
+    if let Some(other) = vertex.uniform(&uniform.name) {
+      if (&other.type_name, other.array_len) != (&uniform.type_name, uniform.array_len) {
+        problems.push(format!(
+          "uniform `{}` is `{}` in vertex shader but `{}` in fragment shader",
+          uniform.name,
+          type_label(other),
+          type_label(uniform)
+        ));
+      }
+    }
+  }
+  problems
+}
+
+/// problems between attributes of an object, as names and numbers per vertex, and its vertex shader.
+/// declared attributes without data are not problems, the renderer reports them as `ProgramWarning::MissingAttribute`
+pub fn check_attributes(vertex: &ShaderDeclarations, attr_names: &[(String, i8)]) -> Vec<String> {
+  let mut problems = Vec::new();
+  for (name, size) in attr_names {
+    match vertex.attribute(name) {
+      None => problems.push(format!("attribute `{name}` is not declared in vertex shader")),
+      Some(declared) => match declared.components() {
+        Some(components) if components != *size => problems.push(format!(
+          "attribute `{name}` has {size} numbers per vertex, but is declared `{}` at line {}",
+          declared.type_name, declared.line
+        )),
+        Some(_) => {}
+        None => problems.push(format!(
+          "attribute `{name}` is declared `{}` at line {}, only scalars and vectors are supported",
+          declared.type_name, declared.line
+        )),
+      },
+    }
+  }
+  problems
+}
+
+fn type_label(declaration: &Declaration) -> String {
+  match declaration.array_len {
+    Some(len) => format!("{}[{len}]", declaration.type_name),
+    None => declaration.type_name.to_owned(),
+  }
+}
+
+/// `name[4]` into name and length
+fn split_array(word: &str) -> (String, Option<usize>) {
+  match word.split_once('[') {
+    Some((name, len)) => (name.to_owned(), len.trim_end_matches(']').parse().ok()),
+    None => (word.to_owned(), None),
+  }
+}
+
+/// drop `layout(...)` before declarations of GLSL ES 3.00
+fn strip_layout(statement: &str) -> String {
+  let trimmed = statement.trim();
+  match trimmed.strip_prefix("layout") {
+    Some(rest) => match rest.find(')') {
+      Some(end) => rest[end + 1..].to_owned(),
+      None => trimmed.to_owned(),
+    },
+    None => trimmed.to_owned(),
+  }
+}
+
+/// replace comments with spaces, keeping line breaks so lines keep their numbers
+fn strip_comments(source: &str) -> String {
+  let mut result = String::with_capacity(source.len());
+  let mut chars = source.chars().peekable();
+  while let Some(c) = chars.next() {
+    match (c, chars.peek()) {
+      ('/', Some('/')) => {
+        for c in chars.by_ref() {
+          if c == '\n' {
+            result.push('\n');
+            break;
+          }
+        }
+      }
+      ('/', Some('*')) => {
+        chars.next();
+        let mut last = ' ';
+        for c in chars.by_ref() {
+          if c == '\n' {
+            result.push('\n');
+          }
+          if last == '*' && c == '/' {
+            break;
+          }
+          last = c;
+        }
+        result.push(' ');
+      }
+      _ => result.push(c),
+    }
+  }
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const VERTEX: &str = "#version 300 es
+precision highp float;
+// in vec3 a_commented;
+layout(location = 0) in vec3 a_position;
+in highp vec4 a_color, a_extra;
+uniform float u_weights[4];
+uniform mat4 u_matrix;
+out vec2 v_uv;
+#ifdef USE_NORMAL
+in vec3 a_normal;
+#endif
+struct Light { vec3 position; };
+uniform Light u_light;
+float helper(float x);
+void main() {
+  float local = 1.0;
+  gl_Position = vec4(a_position, 1.0);
+}
+";
+
+  fn vertex() -> ShaderDeclarations {
+    ShaderDeclarations::parse(VERTEX, ShaderStage::Vertex)
+  }
+
+  fn names(declarations: &[Declaration]) -> Vec<&str> {
+    declarations.iter().map(|x| x.name.as_str()).collect()
+  }
+
+  #[test]
+  fn parses_global_declarations() {
+    let declarations = vertex();
+    assert_eq!(names(&declarations.attributes), ["a_position", "a_color", "a_extra"]);
+    assert_eq!(names(&declarations.uniforms), ["u_weights", "u_matrix", "u_light"]);
+    assert_eq!(names(&declarations.varyings), ["v_uv"]);
+
+    let position = declarations.attribute("a_position").unwrap();
+    assert_eq!((position.type_name.as_str(), position.line), ("vec3", 4));
+    assert_eq!(declarations.attribute("a_extra").unwrap().type_name, "vec4");
+    assert_eq!(declarations.uniform("u_weights").unwrap().array_len, Some(4));
+    assert_eq!(declarations.uniform("u_matrix").unwrap().components(), None);
+  }
+
+  #[test]
+  fn follows_defines_in_conditions() {
+    let source = format!("#define USE_NORMAL\n{}", VERTEX.replacen("#version 300 es\n", "", 1));
+    let declarations = ShaderDeclarations::parse(&source, ShaderStage::Vertex);
+    assert!(declarations.attribute("a_normal").is_some());
+
+    let source = "#ifdef MISSING\nattribute float a_x;\n#else\nattribute float a_y;\n#endif\n";
+    let declarations = ShaderDeclarations::parse(source, ShaderStage::Vertex);
+    assert_eq!(names(&declarations.attributes), ["a_y"]);
+  }
+
+  #[test]
+  fn reads_inputs_of_fragment_shaders_as_varyings() {
+    let source = "precision mediump float;\nvarying vec2 v_uv;\nin float v_depth;\nuniform sampler2D u_texture;\n";
+    let declarations = ShaderDeclarations::parse(source, ShaderStage::Fragment);
+    assert!(declarations.attributes.is_empty());
+    assert_eq!(names(&declarations.varyings), ["v_uv", "v_depth"]);
+    assert_eq!(names(&declarations.uniforms), ["u_texture"]);
+  }
+
+  #[test]
+  fn checks_attributes_against_data() {
+    let declarations = vertex();
+    let attrs = |xs: &[(&str, i8)]| -> Vec<(String, i8)> { xs.iter().map(|(name, size)| (name.to_string(), *size)).collect() };
+
+    assert!(check_attributes(&declarations, &attrs(&[("a_position", 3), ("a_color", 4), ("a_extra", 4)])).is_empty());
+    // attributes without data are left to warnings of the renderer
+    assert!(check_attributes(&declarations, &attrs(&[("a_position", 3)])).is_empty());
+
+    let problems = check_attributes(&declarations, &attrs(&[("a_position", 2), ("a_missing", 1)]));
+    assert_eq!(problems.len(), 2);
+    assert!(problems[0].contains("`a_position` has 2 numbers per vertex"), "{problems:?}");
+    assert!(problems[1].contains("`a_missing` is not declared"), "{problems:?}");
+  }
+
+  #[test]
+  fn checks_varyings_between_stages() {
+    let fragment = ShaderDeclarations::parse("in vec3 v_uv;\nin float v_depth;\n", ShaderStage::Fragment);
+    let problems = check_stages(&vertex(), &fragment);
+    assert_eq!(problems.len(), 2);
+    assert!(problems[0].contains("`vec2` in vertex shader at line 8"), "{problems:?}");
+    assert!(problems[1].contains("`v_depth` read by fragment shader at line 2"), "{problems:?}");
+  }
+}
@@ -1,25 +1,66 @@
 mod alias;
+pub mod backend;
+mod bounds;
 mod component;
+mod context;
+mod dialect;
+mod error;
+mod glsl;
+mod material;
+mod pick;
+mod post;
+mod preprocess;
 mod primes;
 mod program;
+pub mod raster;
+mod reflect;
+mod renderer;
+mod state;
+mod stereo;
+mod texture;
+mod uniform;
 pub mod viewer;
+mod viewport;
+mod webgl;
 
-use std::cell::RefCell;
-use std::rc::Rc;
 use std::sync::RwLock;
 
-use component::TriadicaElementTree;
 use glam::Vec3;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
-use web_sys::console::log_1;
+use web_sys::console::{error_1, log_1, warn_1};
 use web_sys::Element;
-use web_sys::{WebGl2RenderingContext, WebGlProgram};
-
-pub use alias::{group, object};
-pub use component::{PackedAttrs, TriadicaElement};
-pub use primes::{DrawMode, VertexDataValue};
-pub use program::{cached_link_program, ShaderProgramCaches};
+use web_sys::WebGl2RenderingContext;
+
+pub use alias::{group, indexed_object, instanced_object, material_object, object, points_object};
+pub use bounds::Bounds;
+pub use component::{
+  ComponentCache, Instance, InstanceArrays, InstancedOptions, ObjectId, PackedAttrs, TriadicaElement, TriadicaElementTree,
+};
+pub use context::{ContextEvent, ContextWatcher};
+pub use dialect::{upgrade_shader, GlslVersion, ShaderUpgrade, FRAG_COLOR_OUTPUT};
+pub use error::{Severity, ShaderDiagnostic, ShaderStage, TriadicaError};
+pub use glsl::{check_attributes, check_stages, Declaration, ShaderDeclarations};
+pub use material::Material;
+pub use pick::{
+  decode_pick_id, encode_pick_id, pick_object, pick_tree, pixel_to_clip, PickHit, DEFAULT_PICK_TOLERANCE, PICK_FRAGMENT_SHADER,
+  PICK_ID_UNIFORM, PICK_TARGET,
+};
+pub use post::{RenderTargetSpec, ScreenPass, SCREEN_VERTEX_SHADER};
+pub use preprocess::{origin_of, ShaderLibrary, INSTANCE_SNIPPET, PERSPECTIVE_SNIPPET};
+pub use primes::{DrawMode, IndexType, Indices, VertexDataValue, POINT_SIZE_ATTRIBUTE, POINT_SIZE_UNIFORM};
+pub use program::{
+  cached_link_program, release_program, retire_program, LinkedProgram, ProgramCacheStats, ProgramKey, ShaderProgramCaches,
+  DEFAULT_PROGRAM_CAPACITY,
+};
+pub use reflect::{ActiveVariable, ProgramReflection, ProgramWarning};
+pub use renderer::{DrawStats, Renderer};
+pub use state::{BlendMode, CullFace, PolygonOffset, RenderState};
+pub use stereo::{StereoOptions, StereoOutput, DEFAULT_EYE_SEPARATION};
+pub use texture::{Texture, TextureFilter, TextureId, TextureOptions, TextureSource, TextureWrap};
+pub use uniform::{FlatUniform, UniformKind, UniformMap, UniformValue};
+pub use viewport::{Viewport, ViewportRect};
+pub use webgl::{WebGlBackend, WebGlRenderTarget};
 
 use viewer::is_zero;
 
@@ -38,119 +79,22 @@ pub fn request_animation_frame(f: &Closure<dyn FnMut()>) {
     .expect("should register `requestAnimationFrame` OK");
 }
 
-fn bind_attributes(
-  context: &WebGl2RenderingContext,
-  program: &WebGlProgram,
-  attr_name: &str,
-  unit_size: i32,
-  vertices: &[f32],
-) -> Result<(), JsValue> {
-  // web_sys::console::log_1(&format!("{:?}", vertices).into());
-
-  let position_attribute_location = context.get_attrib_location(program, attr_name);
-  let buffer = context.create_buffer().ok_or("Failed to create buffer")?;
-  context.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&buffer));
-
-  // Note that `Float32Array::view` is somewhat dangerous (hence the
-  // `unsafe`!). This is creating a raw view into our module's
-  // `WebAssembly.Memory` buffer, but if we allocate more pages for ourself
-  // (aka do a memory allocation in Rust) it'll cause the buffer to change,
-  // causing the `Float32Array` to be invalid.
-  //
-  // As a result, after `Float32Array::view` we have to be very careful not to
-  // do any memory allocations before it's dropped.
-  unsafe {
-    let positions_array_buf_view = js_sys::Float32Array::view(vertices);
-
-    context.buffer_data_with_array_buffer_view(
-      WebGl2RenderingContext::ARRAY_BUFFER,
-      &positions_array_buf_view,
-      WebGl2RenderingContext::STATIC_DRAW,
-    );
-  }
-
-  let vao = context.create_vertex_array().ok_or("Could not create vertex array object")?;
-  context.bind_vertex_array(Some(&vao));
-
-  context.vertex_attrib_pointer_with_i32(0, unit_size, WebGl2RenderingContext::FLOAT, false, 0, 0);
-  context.enable_vertex_attrib_array(position_attribute_location as u32);
-
-  Ok(())
-}
-
-/// bind float number to uniform
-fn bind_uniform_location(context: &WebGl2RenderingContext, program: &WebGlProgram, variable: &str, value: f32) -> Result<(), JsValue> {
-  let location = context.get_uniform_location(program, variable);
-  context.uniform1f(location.as_ref(), value);
-  Ok(())
-}
-
-/// bind vec3 to uniform
-fn bind_uniform3f_location(
-  context: &WebGl2RenderingContext,
-  program: &WebGlProgram,
-  variable: &str,
-  value: Vec3,
-) -> Result<(), JsValue> {
-  let location = context.get_uniform_location(program, variable);
-  context.uniform3f(location.as_ref(), value.x, value.y, value.z);
-  Ok(())
-}
-
-fn bind_uniforms(context: &WebGl2RenderingContext, program: &WebGlProgram) -> Result<(), JsValue> {
-  let (forward, upward, rightward) = viewer::get_directions();
-
-  // directions
-  bind_uniform3f_location(context, program, "forward", forward)?;
-  bind_uniform3f_location(context, program, "upward", upward)?;
-  bind_uniform3f_location(context, program, "rightward", rightward)?;
-
-  // lookDistance, defaults to 600
-  bind_uniform_location(context, program, "lookDistance", 600.0)?;
-
-  // backcone scale
-  bind_uniform_location(context, program, "coneBackScale", 0.5)?;
-
-  // viewportRatio
-  let window_ratio = *WINDOW_RATIO.read().expect("to get window ratio");
-  bind_uniform_location(context, program, "viewportRatio", window_ratio)?;
-
-  // cameraPosition
-  let pos = viewer::get_camera_position();
-  bind_uniform3f_location(context, program, "cameraPosition", pos)?;
-  // log_2(&"pos".into(), &format!("{:?}", pos).into());
-
-  Ok(())
-}
-
-pub fn paint_canvas(context: &WebGl2RenderingContext, tree: &TriadicaElementTree, caches: Rc<RefCell<ShaderProgramCaches>>) {
-  // context.color_mask(false, false, false, false);
-  context.clear_color(0.0, 0.0, 0.0, 1.0);
-  context.clear(WebGl2RenderingContext::COLOR_BUFFER_BIT);
-
+/// paint with WebGL, objects are uploaded once and reused in later frames.
+/// errors are also printed to console, shader errors with annotated source lines, and warnings of programs after them
+pub fn paint_canvas(renderer: &mut Renderer<WebGlBackend>, tree: &TriadicaElementTree) -> Result<(), TriadicaError> {
   log_1(&"paint".into());
 
-  for item in tree.to_list() {
-    let program = cached_link_program(context, &item.vertex_shader, &item.fragment_shader, caches.clone()).unwrap();
-    context.use_program(Some(&program));
-    bind_uniforms(context, &program).expect("to bind uniforms");
-    if item.arrays.len() != item.attr_names.len() {
-      // TODO
-      panic!("arrays and attr_names should have same length: {:?}", item.attr_names);
-    }
-    for (idx, data) in item.arrays.iter().enumerate() {
-      let (a_name, a_size) = &item.attr_names[idx];
-      bind_attributes(context, &program, a_name, *a_size as i32, data).expect("bind attrs");
-    }
-    context.draw_arrays(item.draw_mode.into(), 0, item.size as i32);
+  let painted = renderer.paint(tree).inspect_err(|e| error_1(&e.to_string().into()));
+  for warning in renderer.take_warnings() {
+    warn_1(&warning.to_string().into());
   }
+  painted
 }
 
 pub fn context_setup(context: &WebGl2RenderingContext) {
   context.enable(WebGl2RenderingContext::DEPTH_TEST);
   context.depth_func(WebGl2RenderingContext::LESS);
-  // context.blend_func(WebGl2RenderingContext::ONE, WebGl2RenderingContext::ONE_MINUS_SRC_ALPHA);
-  // context.depth_mask(false);
+  // blending and depth writes are set per object, see `RenderState`
 }
 
 /// handle events from touch control and move the camera
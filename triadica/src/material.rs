@@ -0,0 +1,213 @@
+//! built-in materials, generating shaders, attribute names and uniforms for common looks
+
+use std::rc::Rc;
+
+use glam::{Vec3, Vec4};
+
+use crate::component::{ComponentOptions, PackedAttrs};
+use crate::dialect::{upgrade_shader, GlslVersion};
+use crate::error::ShaderStage;
+use crate::preprocess::ShaderLibrary;
+use crate::primes::DrawMode;
+use crate::state::RenderState;
+use crate::uniform::{UniformMap, UniformValue};
+
+/// a shader pair with parameters, vertices are placed with the triadica camera.
+/// every material reads `a_position`(vec3), some read more attributes, see `attr_names`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Material {
+  /// one color for every fragment, bound to `u_color`
+  Flat { color: Vec4 },
+  /// color of each vertex from `a_color`(vec4)
+  VertexColor,
+  /// color of the demo, `far_color` turns into `near_color` within `fade_range` of look distance
+  DepthFaded {
+    near_color: Vec3,
+    far_color: Vec3,
+    fade_range: f32,
+  },
+  /// diffuse lighting of a directional light, normals from `a_normal`(vec3)
+  Lambert {
+    color: Vec3,
+    light_direction: Vec3,
+    /// brightness of faces turned away from the light, `0..1`
+    ambient: f32,
+  },
+}
+
+impl Material {
+  pub fn flat(color: Vec4) -> Self {
+    Material::Flat { color }
+  }
+
+  /// same coloring as `demo.frag`
+  pub fn depth_faded() -> Self {
+    Material::DepthFaded {
+      near_color: Vec3::new(1.6, 1.6, 0.0),
+      far_color: Vec3::new(0.6, 0.6, 1.0),
+      fade_range: 10.0,
+    }
+  }
+
+  /// light coming from above, slightly from the front
+  pub fn lambert(color: Vec3) -> Self {
+    Material::Lambert {
+      color,
+      light_direction: Vec3::new(0.0, -1.0, -0.4),
+      ambient: 0.2,
+    }
+  }
+
+  /// attributes the vertex shader reads, in order of data in `PackedAttrs` items
+  pub fn attr_names(&self) -> Vec<(String, i8)> {
+    let mut names = vec![("a_position".to_owned(), 3)];
+    match self {
+      Material::VertexColor => names.push(("a_color".to_owned(), 4)),
+      Material::Lambert { .. } => names.push(("a_normal".to_owned(), 3)),
+      Material::Flat { .. } | Material::DepthFaded { .. } => {}
+    }
+    names
+  }
+
+  /// built-in sources are 1.00, and up-converted for 3.00
+  pub fn vertex_shader(&self, version: GlslVersion) -> String {
+    let mut library = ShaderLibrary::default();
+    match self {
+      Material::VertexColor => library.define("USE_VERTEX_COLOR", 1),
+      Material::Lambert { .. } => library.define("USE_NORMAL", 1),
+      Material::Flat { .. } | Material::DepthFaded { .. } => {}
+    }
+    let source = library
+      .expand("triadica/material.vert", include_str!("../shaders/materials/material.vert"))
+      .expect("built-in snippets to expand");
+    match version {
+      GlslVersion::Es100 => source,
+      GlslVersion::Es300 => upgrade_shader(&source, ShaderStage::Vertex).into_owned(),
+    }
+  }
+
+  pub fn fragment_shader(&self, version: GlslVersion) -> String {
+    let source = match self {
+      Material::Flat { .. } => include_str!("../shaders/materials/flat.frag"),
+      Material::VertexColor => include_str!("../shaders/materials/vertex_color.frag"),
+      Material::DepthFaded { .. } => include_str!("../shaders/materials/depth_faded.frag"),
+      Material::Lambert { .. } => include_str!("../shaders/materials/lambert.frag"),
+    };
+    match version {
+      GlslVersion::Es100 => source.to_owned(),
+      GlslVersion::Es300 => upgrade_shader(source, ShaderStage::Fragment).into_owned(),
+    }
+  }
+
+  /// parameters by uniform name
+  pub fn uniforms(&self) -> UniformMap {
+    let mut uniforms = UniformMap::new();
+    match self {
+      Material::Flat { color } => {
+        uniforms.insert("u_color".to_owned(), UniformValue::Vec4(*color));
+      }
+      Material::VertexColor => {}
+      Material::DepthFaded {
+        near_color,
+        far_color,
+        fade_range,
+      } => {
+        uniforms.insert("u_near_color".to_owned(), UniformValue::Vec3(*near_color));
+        uniforms.insert("u_far_color".to_owned(), UniformValue::Vec3(*far_color));
+        uniforms.insert("u_fade_range".to_owned(), UniformValue::Float(*fade_range));
+      }
+      Material::Lambert {
+        color,
+        light_direction,
+        ambient,
+      } => {
+        uniforms.insert("u_color".to_owned(), UniformValue::Vec3(*color));
+        uniforms.insert("u_light_direction".to_owned(), UniformValue::Vec3(*light_direction));
+        uniforms.insert("u_ambient".to_owned(), UniformValue::Float(*ambient));
+      }
+    }
+    uniforms
+  }
+
+  /// options of an object drawn with this material in GLSL ES 3.00, `packed_attrs` items follow `attr_names`
+  pub fn component(&self, draw_mode: DrawMode, packed_attrs: PackedAttrs) -> ComponentOptions {
+    let material = self.clone();
+    ComponentOptions {
+      draw_mode,
+      vertex_shader: self.vertex_shader(GlslVersion::Es300),
+      fragment_shader: self.fragment_shader(GlslVersion::Es300),
+      attr_names: self.attr_names(),
+      packed_attrs,
+      indices: None,
+      textures: Vec::new(),
+      render_state: RenderState::default(),
+      point_size: 1.0,
+      culling: true,
+      get_uniforms: Rc::new(move || material.uniforms()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alias::material_object;
+  use crate::glsl::{check_attributes, check_stages, ShaderDeclarations};
+  use crate::primes::VertexDataValue;
+
+  fn materials() -> Vec<Material> {
+    vec![
+      Material::flat(Vec4::new(1.0, 0.5, 0.0, 1.0)),
+      Material::VertexColor,
+      Material::depth_faded(),
+      Material::lambert(Vec3::ONE),
+    ]
+  }
+
+  #[test]
+  fn expands_shaders_matching_attributes_and_uniforms() {
+    for material in materials() {
+      for version in [GlslVersion::Es100, GlslVersion::Es300] {
+        let vertex_source = material.vertex_shader(version);
+        let fragment_source = material.fragment_shader(version);
+        assert!(!vertex_source.contains("#include"), "{material:?}");
+        assert_eq!(GlslVersion::of(&vertex_source), version, "{material:?}");
+        assert_eq!(GlslVersion::of(&fragment_source), version, "{material:?}");
+
+        let vertex = ShaderDeclarations::parse(&vertex_source, ShaderStage::Vertex);
+        let fragment = ShaderDeclarations::parse(&fragment_source, ShaderStage::Fragment);
+        assert_eq!(
+          check_attributes(&vertex, &material.attr_names()),
+          Vec::<String>::new(),
+          "{material:?}"
+        );
+        assert_eq!(check_stages(&vertex, &fragment), Vec::<String>::new(), "{material:?}");
+        for (name, value) in material.uniforms() {
+          let declared = vertex.uniform(&name).or_else(|| fragment.uniform(&name));
+          let declared = declared.unwrap_or_else(|| panic!("`{name}` of {material:?} is not declared"));
+          assert_eq!(declared.type_name, value.kind().unwrap().glsl_type(), "`{name}` of {material:?}");
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn compiles_objects_of_every_material() {
+    for material in materials() {
+      let vertex: Vec<VertexDataValue> = material
+        .attr_names()
+        .iter()
+        .map(|(_, size)| match size {
+          3 => VertexDataValue::Vec3([0.0, 1.0, 0.0]),
+          _ => VertexDataValue::Vec4([1.0; 4]),
+        })
+        .collect();
+      let packed_attrs = PackedAttrs::List(vec![PackedAttrs::Item(vertex); 3]);
+      assert!(material.component(DrawMode::Triangles, packed_attrs.clone()).validate().is_ok());
+      let tree = material_object(&material, DrawMode::Triangles, packed_attrs)
+        .compile_to_tree()
+        .unwrap_or_else(|e| panic!("{material:?}: {e}"));
+      assert_eq!((tree.objects()[0].get_uniforms)(), material.uniforms());
+    }
+  }
+}
@@ -0,0 +1,550 @@
+//! finding the object under a pixel, by casting a ray through the cone-back perspective on CPU,
+//! or by reading back a pass of object IDs from GPU, see `Renderer::pick_by_id`
+
+use glam::{Vec2, Vec3, Vec4};
+
+use crate::component::{ComponentCache, ObjectId, TriadicaElementTree};
+use crate::error::TriadicaError;
+use crate::primes::DrawMode;
+use crate::raster::{instance_transform, point_sizes, positions_of, vertex_order};
+use crate::state::CullFace;
+use crate::viewer::{Camera, Ray, CLIP_SCALE};
+
+/// distance in pixels within which lines and points are hit
+pub const DEFAULT_PICK_TOLERANCE: f32 = 4.0;
+
+/// render target of ID passes, declared by `Renderer::pick_by_id` at first use
+pub const PICK_TARGET: &str = "triadica:pick";
+
+/// uniform of ID passes, set to the color of the object drawn
+pub const PICK_ID_UNIFORM: &str = "u_pick_id";
+
+/// fragment shader of ID passes, linked with vertex shaders of objects
+pub const PICK_FRAGMENT_SHADER: &str = "
+precision highp float;
+
+uniform vec4 u_pick_id;
+
+void main() {
+  gl_FragColor = u_pick_id;
+}
+";
+
+/// color of an ID in ID passes, 24 bits in red, green and blue. 0 is the cleared background
+pub fn encode_pick_id(id: u32) -> Vec4 {
+  let channel = |shift: u32| ((id >> shift) & 0xff) as f32 / 255.0;
+  Vec4::new(channel(0), channel(8), channel(16), 1.0)
+}
+
+/// ID of a pixel read back from an ID pass, inverse of `encode_pick_id`
+pub fn decode_pick_id(pixel: [u8; 4]) -> u32 {
+  u32::from_le_bytes([pixel[0], pixel[1], pixel[2], 0])
+}
+
+/// an object under a pixel, with the part of it nearest to the pixel
+#[derive(Debug, Clone, PartialEq)]
+pub struct PickHit {
+  /// indices of children from the root group down to the object, see `TriadicaElement::at`
+  pub path: Vec<usize>,
+  pub object: ObjectId,
+  /// instance hit, `None` for objects that are not instanced
+  pub instance: Option<usize>,
+  /// vertex of the hit primitive nearest to the pixel on screen, index into vertices of the object
+  pub vertex: usize,
+  /// hit segment of lines, or edge of a hit triangle nearest to the pixel, as indices of vertices. `None` for points
+  pub segment: Option<[usize; 2]>,
+  /// point hit, in world space
+  pub position: Vec3,
+  /// `r` of the cone-back perspective at the hit, smaller is closer
+  pub depth: f32,
+}
+
+/// clip space position of a pixel, in pixels of the drawing buffer from the top left like mouse events
+pub fn pixel_to_clip(size: (i32, i32), pixel: Vec2) -> Vec2 {
+  Vec2::new(pixel.x / size.0 as f32 * 2.0 - 1.0, 1.0 - pixel.y / size.1 as f32 * 2.0)
+}
+
+/// nearest object of the tree under a pixel of a target of `size`, drawn with `camera`.
+/// triangles are hit by a ray, lines and points within `tolerance` pixels.
+/// objects without `a_position` are skipped, and vertex shaders are assumed to only project positions
+pub fn pick_tree(
+  tree: &TriadicaElementTree,
+  camera: &Camera,
+  size: (i32, i32),
+  pixel: Vec2,
+  tolerance: f32,
+) -> Result<Option<PickHit>, TriadicaError> {
+  let mut nearest: Option<PickHit> = None;
+  for (path, item) in tree.objects_with_paths() {
+    if let Some(hit) = pick_object(&path, item, camera, size, pixel, tolerance)? {
+      if nearest.as_ref().is_none_or(|x| hit.depth < x.depth) {
+        nearest = Some(hit);
+      }
+    }
+  }
+  Ok(nearest)
+}
+
+/// like `pick_tree` for one object at `path`
+pub fn pick_object(
+  path: &[usize],
+  item: &ComponentCache,
+  camera: &Camera,
+  size: (i32, i32),
+  pixel: Vec2,
+  tolerance: f32,
+) -> Result<Option<PickHit>, TriadicaError> {
+  if item.is_empty() {
+    return Ok(None);
+  }
+  let picker = Picker {
+    camera,
+    size,
+    ray: camera.ray_at(pixel_to_clip(size, pixel)),
+  };
+  let sizes = point_sizes(item);
+  let mut nearest: Option<PickHit> = None;
+  for (instance, positions) in instance_positions(item)? {
+    let order = vertex_order(item, positions.len())?;
+    let projected: Vec<Option<ScreenVertex>> = positions.iter().map(|p| picker.project(*p)).collect();
+    let at = |k: usize| projected[order[k]].map(|v| (order[k], v));
+
+    for shape in shapes(item.draw_mode, order.len()) {
+      let hit = match shape {
+        Shape::Point(k) => at(k).and_then(|(idx, v)| {
+          let half = sizes.and_then(|xs| xs.get(idx)).copied().unwrap_or(item.point_size) * 0.5 + tolerance;
+          let offset = (v.window - pixel).abs();
+          (offset.x <= half && offset.y <= half).then_some((idx, None, positions[idx], v.depth))
+        }),
+        Shape::Segment([a, b]) => at(a).zip(at(b)).and_then(|((ia, va), (ib, vb))| {
+          let (distance, u) = distance_to_segment(pixel, va.window, vb.window);
+          let vertex = if u < 0.5 { ia } else { ib };
+          let position = positions[ia].lerp(positions[ib], u);
+          (distance <= tolerance).then_some((vertex, Some([ia, ib]), position, va.depth + (vb.depth - va.depth) * u))
+        }),
+        Shape::Triangle([a, b, c]) => match (at(a), at(b), at(c)) {
+          (Some((ia, va)), Some((ib, vb)), Some((ic, vc))) => {
+            // window y grows downward, so front faces wind clockwise here
+            let front = edge_cross(va.window, vb.window, vc.window) < 0.0;
+            let culled = match item.render_state.cull_face {
+              CullFace::None => false,
+              CullFace::Back => !front,
+              CullFace::Front => front,
+            };
+            let t = if culled {
+              None
+            } else {
+              picker.intersect(positions[ia], positions[ib], positions[ic])
+            };
+            t.map(|t| {
+              let vertex = nearest_of(pixel, [(ia, va), (ib, vb), (ic, vc)]);
+              let edges = [[(ia, va), (ib, vb)], [(ib, vb), (ic, vc)], [(ic, vc), (ia, va)]];
+              let [(ea, _), (eb, _)] = edges
+                .into_iter()
+                .min_by(|x, y| {
+                  let dx = distance_to_segment(pixel, x[0].1.window, x[1].1.window).0;
+                  let dy = distance_to_segment(pixel, y[0].1.window, y[1].1.window).0;
+                  dx.total_cmp(&dy)
+                })
+                .expect("three edges");
+              (vertex, Some([ea, eb]), picker.ray.at(t), camera.ray_depth(t))
+            })
+          }
+          _ => None,
+        },
+      };
+      if let Some((vertex, segment, position, depth)) = hit {
+        if nearest.as_ref().is_none_or(|x| depth < x.depth) {
+          nearest = Some(PickHit {
+            path: path.to_vec(),
+            object: item.id,
+            instance,
+            vertex,
+            segment,
+            position,
+            depth,
+          });
+        }
+      }
+    }
+  }
+  Ok(nearest)
+}
+
+/// vertex of the object nearest to the pixel on screen, for objects known to be under the pixel
+/// whose primitives are not hit on CPU, like ones moved by their vertex shaders
+pub(crate) fn nearest_vertex(
+  path: &[usize],
+  item: &ComponentCache,
+  camera: &Camera,
+  size: (i32, i32),
+  pixel: Vec2,
+) -> Result<Option<PickHit>, TriadicaError> {
+  let picker = Picker {
+    camera,
+    size,
+    ray: camera.ray_at(pixel_to_clip(size, pixel)),
+  };
+  let mut nearest: Option<(f32, PickHit)> = None;
+  for (instance, positions) in instance_positions(item)? {
+    for (idx, position) in positions.iter().enumerate() {
+      if let Some(v) = picker.project(*position) {
+        let distance = v.window.distance(pixel);
+        if nearest.as_ref().is_none_or(|(d, _)| distance < *d) {
+          let hit = PickHit {
+            path: path.to_vec(),
+            object: item.id,
+            instance,
+            vertex: idx,
+            segment: None,
+            position: *position,
+            depth: v.depth,
+          };
+          nearest = Some((distance, hit));
+        }
+      }
+    }
+  }
+  Ok(nearest.map(|(_, hit)| hit))
+}
+
+/// index of an instance and its positions in world space
+type InstancePositions = (Option<usize>, Vec<Vec3>);
+
+/// positions of each instance, a single `None` instance for objects that are not instanced
+fn instance_positions(item: &ComponentCache) -> Result<Vec<InstancePositions>, TriadicaError> {
+  let positions = match positions_of(item)? {
+    Some(positions) => positions,
+    None => return Ok(Vec::new()),
+  };
+  match &item.instances {
+    Some(instances) => Ok(
+      (0..instances.count)
+        .map(|idx| {
+          let transform = instance_transform(instances, idx);
+          (Some(idx), positions.iter().map(|p| transform.transform_point3(*p)).collect())
+        })
+        .collect(),
+    ),
+    None => Ok(vec![(None, positions)]),
+  }
+}
+
+/// a vertex projected into window pixels, with `r` as depth
+#[derive(Debug, Clone, Copy)]
+struct ScreenVertex {
+  window: Vec2,
+  depth: f32,
+}
+
+struct Picker<'a> {
+  camera: &'a Camera,
+  size: (i32, i32),
+  ray: Ray,
+}
+
+impl Picker<'_> {
+  /// `None` for points hidden by the perspective, behind the camera or beyond the far plane
+  fn project(&self, position: Vec3) -> Option<ScreenVertex> {
+    let p = self.camera.transform_perspective(position);
+    let z = p.point.z * CLIP_SCALE;
+    if p.r < p.s * -0.9 || z > 1.0 {
+      return None;
+    }
+    let clip = p.point * CLIP_SCALE;
+    Some(ScreenVertex {
+      window: Vec2::new((clip.x + 1.0) * 0.5 * self.size.0 as f32, (1.0 - clip.y) * 0.5 * self.size.1 as f32),
+      depth: p.r,
+    })
+  }
+
+  /// `t` of the ray hitting a triangle, within the range the perspective shows
+  fn intersect(&self, a: Vec3, b: Vec3, c: Vec3) -> Option<f32> {
+    let (ab, ac) = (b - a, c - a);
+    let p = self.ray.direction.cross(ac);
+    let det = ab.dot(p);
+    if det.abs() < f32::EPSILON {
+      return None;
+    }
+    let to_origin = self.ray.origin - a;
+    let u = to_origin.dot(p) / det;
+    let q = to_origin.cross(ab);
+    let v = self.ray.direction.dot(q) / det;
+    if u < 0.0 || v < 0.0 || u + v > 1.0 {
+      return None;
+    }
+    let t = ac.dot(q) / det;
+    let depth = self.camera.ray_depth(t);
+    let s = self.camera.cone_back_scale;
+    (depth >= s * -0.9 && depth * CLIP_SCALE <= 1.0).then_some(t)
+  }
+}
+
+/// primitives of a draw mode, as positions in the drawn order of vertices
+#[derive(Debug, PartialEq)]
+enum Shape {
+  Point(usize),
+  Segment([usize; 2]),
+  Triangle([usize; 3]),
+}
+
+/// same primitives as GL assembles, odd triangles of strips are flipped to keep their winding
+fn shapes(mode: DrawMode, count: usize) -> Vec<Shape> {
+  match mode {
+    DrawMode::Points => (0..count).map(Shape::Point).collect(),
+    DrawMode::Lines => (0..count / 2).map(|i| Shape::Segment([i * 2, i * 2 + 1])).collect(),
+    DrawMode::LineStrip => (1..count).map(|i| Shape::Segment([i - 1, i])).collect(),
+    DrawMode::LineLoop => {
+      let mut segments: Vec<Shape> = (1..count).map(|i| Shape::Segment([i - 1, i])).collect();
+      if count > 2 {
+        segments.push(Shape::Segment([count - 1, 0]));
+      }
+      segments
+    }
+    DrawMode::Triangles => (0..count / 3).map(|i| Shape::Triangle([i * 3, i * 3 + 1, i * 3 + 2])).collect(),
+    DrawMode::TriangleStrip => (2..count)
+      .map(|i| {
+        if i % 2 == 0 {
+          Shape::Triangle([i - 2, i - 1, i])
+        } else {
+          Shape::Triangle([i - 1, i - 2, i])
+        }
+      })
+      .collect(),
+    DrawMode::TriangleFan => (2..count).map(|i| Shape::Triangle([0, i - 1, i])).collect(),
+  }
+}
+
+/// distance from a point to a segment, and where the nearest point is along it in `0..1`
+fn distance_to_segment(p: Vec2, a: Vec2, b: Vec2) -> (f32, f32) {
+  let ab = b - a;
+  let length_squared = ab.length_squared();
+  let u = if length_squared < f32::EPSILON {
+    0.0
+  } else {
+    ((p - a).dot(ab) / length_squared).clamp(0.0, 1.0)
+  };
+  (p.distance(a + ab * u), u)
+}
+
+fn edge_cross(a: Vec2, b: Vec2, c: Vec2) -> f32 {
+  (b - a).perp_dot(c - a)
+}
+
+fn nearest_of(pixel: Vec2, vertices: [(usize, ScreenVertex); 3]) -> usize {
+  let (idx, _) = vertices
+    .into_iter()
+    .min_by(|(_, x), (_, y)| x.window.distance(pixel).total_cmp(&y.window.distance(pixel)))
+    .expect("three vertices");
+  idx
+}
+
+#[cfg(test)]
+mod tests {
+  use std::rc::Rc;
+
+  use super::*;
+  use crate::alias::{group, instanced_object, object, points_object};
+  use crate::component::{Instance, PackedAttrs, TriadicaElement};
+  use crate::primes::VertexDataValue;
+  use crate::uniform::UniformMap;
+
+  const VERTEX: &str = "#version 300 es
+in vec3 a_position;
+void main() {
+  gl_Position = vec4(a_position, 1.0);
+}
+";
+
+  const INSTANCED_VERTEX: &str = "#version 300 es
+in vec3 a_position;
+in vec3 a_offset;
+in float a_scale;
+in vec4 a_color;
+in vec4 a_rotation;
+void main() {
+  gl_Position = vec4(a_position + a_offset, 1.0);
+}
+";
+
+  const FRAGMENT: &str = "#version 300 es
+precision mediump float;
+out vec4 color;
+void main() {
+  color = vec4(1.0);
+}
+";
+
+  const SIZE: (i32, i32) = (600, 600);
+
+  /// at `z = 600` facing the origin, a world unit at `z = 0` is 0.6 pixels, and `+x` of world is to the left
+  fn camera() -> Camera {
+    Camera::looking_at(Vec3::new(0.0, 0.0, 600.0), Vec3::ZERO, Vec3::Y)
+  }
+
+  fn center() -> Vec2 {
+    Vec2::new(300.0, 300.0)
+  }
+
+  fn positions(points: &[[f32; 3]]) -> PackedAttrs {
+    PackedAttrs::List(points.iter().map(|p| PackedAttrs::Item(vec![VertexDataValue::Vec3(*p)])).collect())
+  }
+
+  fn shape(draw_mode: DrawMode, points: &[[f32; 3]]) -> TriadicaElement {
+    object(
+      draw_mode,
+      VERTEX.to_owned(),
+      FRAGMENT.to_owned(),
+      vec![("a_position".to_owned(), 3)],
+      positions(points),
+      Rc::new(UniformMap::default),
+    )
+  }
+
+  fn triangle(z: f32) -> TriadicaElement {
+    shape(DrawMode::Triangles, &[[-100.0, -100.0, z], [100.0, -100.0, z], [0.0, 100.0, z]])
+  }
+
+  fn pick(tree: &TriadicaElement, pixel: Vec2) -> Option<PickHit> {
+    pick_tree(&tree.compile_to_tree().unwrap(), &camera(), SIZE, pixel, DEFAULT_PICK_TOLERANCE).unwrap()
+  }
+
+  #[test]
+  fn casts_rays_through_projected_points() {
+    let camera = Camera {
+      viewport_ratio: 0.5,
+      ..Camera::looking_at(Vec3::new(40.0, -30.0, 500.0), Vec3::new(10.0, 20.0, 0.0), Vec3::Y)
+    };
+    for p in [Vec3::ZERO, Vec3::new(120.0, -80.0, 40.0), Vec3::new(-300.0, 200.0, -900.0)] {
+      let clip = camera.clip_position(p);
+      let ray = camera.ray_at(Vec2::new(clip.x, clip.y));
+      let t = (p - ray.origin).dot(camera.forward);
+      assert!(ray.at(t).distance(p) < 1e-2, "{:?} for {p:?}", ray.at(t));
+      assert!((camera.ray_depth(t) - camera.transform_perspective(p).r).abs() < 1e-4);
+    }
+  }
+
+  #[test]
+  fn converts_pixels_and_ids() {
+    assert_eq!(pixel_to_clip(SIZE, Vec2::ZERO), Vec2::new(-1.0, 1.0));
+    assert_eq!(pixel_to_clip(SIZE, Vec2::new(600.0, 600.0)), Vec2::new(1.0, -1.0));
+    assert_eq!(pixel_to_clip(SIZE, center()), Vec2::ZERO);
+    for id in [0, 1, 255, 256, 0xabcdef] {
+      let color = encode_pick_id(id);
+      let pixel = color.to_array().map(|x| (x * 255.0).round() as u8);
+      assert_eq!(decode_pick_id(pixel), id);
+    }
+  }
+
+  #[test]
+  fn picks_nearest_triangle() {
+    let tree = group(vec![triangle(0.0), triangle(100.0), triangle(-100.0)]);
+    let hit = pick(&tree, center()).expect("hit");
+    assert_eq!(hit.path, [1]);
+    assert_eq!(hit.instance, None);
+    assert!(hit.position.distance(Vec3::new(0.0, 0.0, 100.0)) < 1e-2, "{:?}", hit.position);
+    assert!((hit.depth - camera().transform_perspective(hit.position).r).abs() < 1e-4);
+    // nearest corner on screen is the top one
+    assert_eq!(hit.vertex, 2);
+
+    assert_eq!(pick(&tree, Vec2::new(300.0, 10.0)), None);
+  }
+
+  #[test]
+  fn picks_lines_within_tolerance() {
+    // 5 units above the center is 3 pixels on screen
+    let line = shape(DrawMode::Lines, &[[-200.0, 5.0, 0.0], [200.0, 5.0, 0.0]]);
+    let hit = pick(&line, center()).expect("hit");
+    assert_eq!(hit.segment, Some([0, 1]));
+    assert!(hit.position.distance(Vec3::new(0.0, 5.0, 0.0)) < 1e-2, "{:?}", hit.position);
+
+    let far = shape(DrawMode::Lines, &[[-200.0, 20.0, 0.0], [200.0, 20.0, 0.0]]);
+    assert_eq!(pick(&far, center()), None);
+  }
+
+  #[test]
+  fn picks_instances() {
+    let instances = [
+      Instance::default(),
+      Instance {
+        offset: [200.0, 0.0, 0.0],
+        ..Instance::default()
+      },
+    ];
+    let small = positions(&[[-20.0, -20.0, 0.0], [20.0, -20.0, 0.0], [0.0, 20.0, 0.0]]);
+    let tree = instanced_object(
+      DrawMode::Triangles,
+      INSTANCED_VERTEX.to_owned(),
+      FRAGMENT.to_owned(),
+      vec![("a_position".to_owned(), 3)],
+      small,
+      &instances,
+      Rc::new(UniformMap::default),
+    );
+    // `+x` of world is to the left, 200 units are 120 pixels
+    let hit = pick(&tree, Vec2::new(180.0, 300.0)).expect("hit");
+    assert_eq!(hit.instance, Some(1));
+    assert!(hit.position.distance(Vec3::new(200.0, 0.0, 0.0)) < 1e-2, "{:?}", hit.position);
+    assert_eq!(pick(&tree, center()).map(|x| x.instance), Some(Some(0)));
+  }
+
+  #[test]
+  fn splits_draw_modes_into_shapes() {
+    assert_eq!(shapes(DrawMode::Points, 2), [Shape::Point(0), Shape::Point(1)]);
+    assert_eq!(
+      shapes(DrawMode::LineLoop, 3),
+      [Shape::Segment([0, 1]), Shape::Segment([1, 2]), Shape::Segment([2, 0])]
+    );
+    assert_eq!(shapes(DrawMode::LineLoop, 2), [Shape::Segment([0, 1])]);
+    assert_eq!(
+      shapes(DrawMode::TriangleFan, 5),
+      [Shape::Triangle([0, 1, 2]), Shape::Triangle([0, 2, 3]), Shape::Triangle([0, 3, 4])]
+    );
+    assert_eq!(
+      shapes(DrawMode::TriangleStrip, 4),
+      [Shape::Triangle([0, 1, 2]), Shape::Triangle([2, 1, 3])]
+    );
+    assert!(shapes(DrawMode::TriangleFan, 2).is_empty());
+  }
+
+  #[test]
+  fn picks_points_by_their_sizes() {
+    let vertex = "#version 300 es
+in vec3 a_position;
+in float a_point_size;
+void main() {
+  gl_PointSize = a_point_size;
+  gl_Position = vec4(a_position, 1.0);
+}
+";
+    let point = |p: [f32; 3], size: f32| PackedAttrs::Item(vec![VertexDataValue::Vec3(p), VertexDataValue::Float(size)]);
+    let sized = points_object(
+      vertex.to_owned(),
+      FRAGMENT.to_owned(),
+      vec![("a_position".to_owned(), 3), ("a_point_size".to_owned(), 1)],
+      PackedAttrs::List(vec![point([0.0, 0.0, 0.0], 20.0), point([-300.0, 0.0, 0.0], 2.0)]),
+      1.0,
+      Rc::new(UniformMap::default),
+    );
+    // half of 20 pixels, and the tolerance
+    assert_eq!(pick(&sized, Vec2::new(313.0, 300.0)).map(|x| x.vertex), Some(0));
+    assert_eq!(pick(&sized, Vec2::new(316.0, 300.0)), None);
+    assert_eq!(pick(&sized, Vec2::new(486.0, 300.0)), None);
+
+    // without `a_point_size`, sizes fall back to the one of the object
+    let by_object = |size: f32| {
+      points_object(
+        VERTEX.to_owned(),
+        FRAGMENT.to_owned(),
+        vec![("a_position".to_owned(), 3)],
+        positions(&[[-300.0, 0.0, 0.0]]),
+        size,
+        Rc::new(UniformMap::default),
+      )
+    };
+    let pixel = Vec2::new(488.0, 300.0);
+    assert_eq!(pick(&by_object(1.0), pixel), None);
+    let hit = pick(&by_object(20.0), pixel).expect("hit");
+    assert_eq!((hit.vertex, hit.segment), (0, None));
+  }
+}
@@ -0,0 +1,101 @@
+//! offscreen render targets and full-screen shader passes, for effects like blur, bloom and color grading
+
+use std::rc::Rc;
+
+use crate::component::{ComponentCache, ComponentOptions, PackedAttrs};
+use crate::primes::{DrawMode, VertexDataValue};
+use crate::state::RenderState;
+use crate::uniform::UniformMap;
+
+/// vertex shader of screen passes, covers the viewport and passes `v_uv` in `0..1`
+pub const SCREEN_VERTEX_SHADER: &str = "
+attribute vec2 a_position;
+
+varying vec2 v_uv;
+
+void main() {
+  v_uv = a_position * 0.5 + 0.5;
+  gl_Position = vec4(a_position, 0.0, 1.0);
+}
+";
+
+/// an offscreen target, sized relative to the canvas so it follows resizing
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderTargetSpec {
+  pub name: String,
+  /// size relative to the canvas, use `0.5` for cheaper blur passes
+  pub scale: f32,
+  /// attach a depth buffer, needed when drawing trees with depth test
+  pub depth: bool,
+}
+
+impl RenderTargetSpec {
+  pub fn new(name: &str, scale: f32, depth: bool) -> Self {
+    Self {
+      name: name.to_owned(),
+      scale,
+      depth,
+    }
+  }
+
+  /// pixel size for a canvas size, at least 1x1
+  pub fn size_for(&self, canvas: (i32, i32)) -> (i32, i32) {
+    (
+      ((canvas.0 as f32 * self.scale).round() as i32).max(1),
+      ((canvas.1 as f32 * self.scale).round() as i32).max(1),
+    )
+  }
+}
+
+/// full-screen quad drawn with a fragment shader reading `v_uv`.
+/// `u_resolution` is set to size of the output in pixels
+#[derive(Debug, Clone)]
+pub struct ScreenPass {
+  pub object: ComponentCache,
+  /// sampler uniform name and the render target it reads
+  pub inputs: Vec<(String, String)>,
+}
+
+impl ScreenPass {
+  pub fn new(fragment_shader: &str, inputs: Vec<(String, String)>, get_uniforms: Rc<dyn Fn() -> UniformMap>) -> Self {
+    let corner = |x: f32, y: f32| PackedAttrs::Item(vec![VertexDataValue::Vec2([x, y])]);
+    let options = ComponentOptions {
+      draw_mode: DrawMode::TriangleStrip,
+      vertex_shader: SCREEN_VERTEX_SHADER.to_owned(),
+      fragment_shader: fragment_shader.to_owned(),
+      attr_names: vec![("a_position".to_owned(), 2)],
+      packed_attrs: PackedAttrs::List(vec![corner(-1., -1.), corner(1., -1.), corner(-1., 1.), corner(1., 1.)]),
+      indices: None,
+      textures: Vec::new(),
+      // covers the whole target, nothing to test against
+      render_state: RenderState {
+        depth_test: false,
+        depth_write: false,
+        ..RenderState::default()
+      },
+      point_size: 1.0,
+      culling: false,
+      get_uniforms,
+    };
+    Self {
+      object: options.compile_attributes(),
+      inputs,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn sizes_targets_relative_to_canvas() {
+    assert_eq!(RenderTargetSpec::new("full", 1.0, true).size_for((800, 600)), (800, 600));
+    let half = RenderTargetSpec::new("half", 0.5, false);
+    assert_eq!(half.size_for((800, 600)), (400, 300));
+    // rounded, and never empty
+    assert_eq!(half.size_for((3, 1)), (2, 1));
+    assert_eq!(half.size_for((0, 0)), (1, 1));
+    assert_eq!(RenderTargetSpec::new("tiny", 0.01, false).size_for((800, 600)), (8, 6));
+  }
+}
@@ -0,0 +1,208 @@
+//! expands `#include "name"` in shaders with registered snippets, and injects `#define`s from Rust.
+//! expanded sources carry origin comments, so compile errors point back to files and lines before expansion
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Display;
+
+use crate::error::TriadicaError;
+
+/// built-in snippet with camera uniforms, `PointResult` and `transform_perspective`, same as in `demo.vert`.
+/// only declares uniforms and functions, so it works in GLSL ES 1.00 and 3.00
+pub const PERSPECTIVE_SNIPPET: &str = "triadica/perspective";
+
+/// built-in snippet with `instance_transform(position, a_offset, a_scale, a_rotation)`, placing a vertex of an instance
+/// the same way `Instance` describes and the software renderer does. only declares functions
+pub const INSTANCE_SNIPPET: &str = "triadica/instance";
+
+/// comment before each expanded segment, followed by `file:line` of the next line
+pub(crate) const ORIGIN_MARKER: &str = "//@origin ";
+
+/// named snippets for `#include`, and defines added to every expanded shader
+#[derive(Debug, Clone)]
+pub struct ShaderLibrary {
+  snippets: BTreeMap<String, String>,
+  defines: Vec<(String, String)>,
+}
+
+impl Default for ShaderLibrary {
+  /// with built-in snippets registered
+  fn default() -> Self {
+    let mut library = Self::empty();
+    library.register(PERSPECTIVE_SNIPPET, include_str!("../shaders/perspective.glsl"));
+    library.register(INSTANCE_SNIPPET, include_str!("../shaders/instance.glsl"));
+    library
+  }
+}
+
+impl ShaderLibrary {
+  /// without built-in snippets
+  pub fn empty() -> Self {
+    Self {
+      snippets: BTreeMap::new(),
+      defines: Vec::new(),
+    }
+  }
+
+  /// add or replace a snippet, snippets may include other snippets
+  pub fn register(&mut self, name: &str, source: &str) {
+    self.snippets.insert(name.to_owned(), source.to_owned());
+  }
+
+  /// add `#define name value` to expanded shaders, a later define of same name replaces the value
+  pub fn define(&mut self, name: &str, value: impl Display) {
+    let value = value.to_string();
+    match self.defines.iter_mut().find(|(n, _)| n == name) {
+      Some(define) => define.1 = value,
+      None => self.defines.push((name.to_owned(), value)),
+    }
+  }
+
+  /// resolve includes of a shader, `file` names it in error messages.
+  /// `#version` stays on first line, defines follow it, and each snippet is included once
+  pub fn expand(&self, file: &str, source: &str) -> Result<String, TriadicaError> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut body: Vec<&str> = source.lines().collect();
+    if let Some(first) = body.first_mut() {
+      if first.trim_start().starts_with("#version") {
+        lines.push(first.to_string());
+        // keep line numbers of the rest
+        *first = "";
+      }
+    }
+    for (name, value) in &self.defines {
+      lines.push(format!("#define {name} {value}"));
+    }
+
+    let mut included = HashSet::new();
+    self.expand_into(file, &body, &mut lines, &mut vec![file.to_owned()], &mut included)?;
+    let mut expanded = lines.join("\n");
+    expanded.push('\n');
+    Ok(expanded)
+  }
+
+  fn expand_into(
+    &self,
+    file: &str,
+    body: &[&str],
+    lines: &mut Vec<String>,
+    stack: &mut Vec<String>,
+    included: &mut HashSet<String>,
+  ) -> Result<(), TriadicaError> {
+    lines.push(format!("{ORIGIN_MARKER}{file}:1"));
+    for (idx, line) in body.iter().enumerate() {
+      let rest = match line.trim_start().strip_prefix("#include") {
+        Some(rest) => rest.trim(),
+        None => {
+          lines.push(line.to_string());
+          continue;
+        }
+      };
+      let error = |message: String| TriadicaError::Preprocess {
+        file: file.to_owned(),
+        line: idx + 1,
+        message,
+      };
+      let name = rest
+        .strip_prefix('"')
+        .and_then(|x| x.strip_suffix('"'))
+        .ok_or_else(|| error(format!("expected `#include \"name\"`, got `{}`", line.trim())))?;
+      if stack.iter().any(|x| x == name) {
+        return Err(error(format!("`{name}` includes itself, through {}", stack.join(" -> "))));
+      }
+      // later includes of a snippet are skipped, like include guards
+      if included.insert(name.to_owned()) {
+        let snippet = self.snippets.get(name).ok_or_else(|| error(format!("unknown snippet `{name}`")))?;
+        let snippet_lines: Vec<&str> = snippet.lines().collect();
+        stack.push(name.to_owned());
+        self.expand_into(name, &snippet_lines, lines, stack, included)?;
+        stack.pop();
+      }
+      lines.push(format!("{ORIGIN_MARKER}{file}:{}", idx + 2));
+    }
+    Ok(())
+  }
+}
+
+/// file and line before expansion of a line in expanded source, lines count from 1.
+/// `None` for sources not expanded by `ShaderLibrary`, or lines added by it
+pub fn origin_of(source: &str, line: usize) -> Option<(String, usize)> {
+  let lines: Vec<&str> = source.lines().collect();
+  let idx = line.checked_sub(1)?;
+  if lines.get(idx)?.starts_with(ORIGIN_MARKER) {
+    return None;
+  }
+  (0..idx).rev().find_map(|marker_idx| {
+    let (file, start) = lines[marker_idx].strip_prefix(ORIGIN_MARKER)?.rsplit_once(':')?;
+    let start: usize = start.parse().ok()?;
+    Some((file.to_owned(), start + idx - marker_idx - 1))
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn library() -> ShaderLibrary {
+    let mut library = ShaderLibrary::empty();
+    library.register("lib/common", "float twice(float x) {\n  return x * 2.0;\n}");
+    library.register(
+      "lib/nested",
+      "#include \"lib/common\"\nfloat four_times(float x) { return twice(twice(x)); }",
+    );
+    library
+  }
+
+  /// line of the expanded source containing `text`, counting from 1
+  fn line_of(source: &str, text: &str) -> usize {
+    source.lines().position(|line| line.contains(text)).expect("text in source") + 1
+  }
+
+  #[test]
+  fn expands_includes_once_and_keeps_version_first() {
+    let source = "#version 300 es\n#include \"lib/nested\"\n#include \"lib/common\"\nvoid main() {}";
+    let mut library = library();
+    library.define("COUNT", 3);
+    let expanded = library.expand("main.vert", source).unwrap();
+    let lines: Vec<&str> = expanded.lines().collect();
+    assert_eq!(lines[..2], ["#version 300 es", "#define COUNT 3"]);
+    assert_eq!(expanded.matches("float twice").count(), 1);
+    assert!(line_of(&expanded, "float twice") < line_of(&expanded, "float four_times"));
+    assert!(!expanded.contains("#include"));
+  }
+
+  #[test]
+  fn maps_expanded_lines_to_origins() {
+    let source = "#version 300 es\n#include \"lib/nested\"\nvoid main() {\n  gl_Position = vec4(0.0);\n}";
+    let expanded = library().expand("main.vert", source).unwrap();
+    let origin = |text: &str| origin_of(&expanded, line_of(&expanded, text));
+    assert_eq!(origin("return x * 2.0"), Some(("lib/common".to_owned(), 2)));
+    assert_eq!(origin("float four_times"), Some(("lib/nested".to_owned(), 2)));
+    assert_eq!(origin("gl_Position"), Some(("main.vert".to_owned(), 4)));
+    // lines added by expansion have no origin
+    assert_eq!(origin_of(&expanded, line_of(&expanded, ORIGIN_MARKER)), None);
+    assert_eq!(origin_of("void main() {}", 1), None);
+  }
+
+  #[test]
+  fn reports_bad_includes_at_their_lines() {
+    let mut library = library();
+    let error = library.expand("main.vert", "void f();\n#include \"lib/missing\"").unwrap_err();
+    assert_eq!(
+      error,
+      TriadicaError::Preprocess {
+        file: "main.vert".to_owned(),
+        line: 2,
+        message: "unknown snippet `lib/missing`".to_owned(),
+      }
+    );
+
+    library.register("lib/loop", "\n#include \"lib/loop\"");
+    match library.expand("main.vert", "#include \"lib/loop\"").unwrap_err() {
+      TriadicaError::Preprocess { file, line, message } => {
+        assert_eq!((file.as_str(), line), ("lib/loop", 2));
+        assert!(message.contains("includes itself"), "{message}");
+      }
+      error => panic!("unexpected {error:?}"),
+    }
+  }
+}
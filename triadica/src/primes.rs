@@ -1,20 +1,38 @@
 use web_sys::WebGl2RenderingContext;
 
-#[derive(Clone, Copy, Debug)]
+use crate::error::TriadicaError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum DrawMode {
-  Triangles,
+  /// one square per vertex, sized by `gl_PointSize`, see `POINT_SIZE_UNIFORM`
+  Points,
   Lines,
   LineStrip,
+  /// like `LineStrip`, connecting last vertex back to first
+  LineLoop,
+  Triangles,
   TriangleStrip,
+  /// triangles sharing first vertex
+  TriangleFan,
 }
 
+/// uniform of object point size, set for `DrawMode::Points` before object uniforms.
+/// vertex shaders write it with `gl_PointSize = u_point_size;`
+pub const POINT_SIZE_UNIFORM: &str = "u_point_size";
+
+/// per-vertex point size by convention, declare it in `attr_names` and use `gl_PointSize = a_point_size;`
+pub const POINT_SIZE_ATTRIBUTE: &str = "a_point_size";
+
 impl From<DrawMode> for u32 {
   fn from(x: DrawMode) -> Self {
     match x {
-      DrawMode::Triangles => WebGl2RenderingContext::TRIANGLES,
+      DrawMode::Points => WebGl2RenderingContext::POINTS,
       DrawMode::Lines => WebGl2RenderingContext::LINES,
       DrawMode::LineStrip => WebGl2RenderingContext::LINE_STRIP,
+      DrawMode::LineLoop => WebGl2RenderingContext::LINE_LOOP,
+      DrawMode::Triangles => WebGl2RenderingContext::TRIANGLES,
       DrawMode::TriangleStrip => WebGl2RenderingContext::TRIANGLE_STRIP,
+      DrawMode::TriangleFan => WebGl2RenderingContext::TRIANGLE_FAN,
     }
   }
 }
@@ -22,7 +40,7 @@ impl From<DrawMode> for u32 {
 /// collection of key/value pairs
 pub type VertexData = Vec<VertexDataValue>;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum VertexDataValue {
   Float(f32),
   Vec2([f32; 2]),
@@ -43,4 +61,131 @@ impl VertexDataValue {
       VertexDataValue::Vec4(_) => 4,
     }
   }
+
+  pub fn to_vec(&self) -> Vec<f32> {
+    match self {
+      VertexDataValue::Float(f) => vec![*f],
+      VertexDataValue::Vec2(v) => v.to_vec(),
+      VertexDataValue::Vec3(v) => v.to_vec(),
+      VertexDataValue::Vec4(v) => v.to_vec(),
+    }
+  }
+}
+
+impl From<glam::Vec3> for VertexDataValue {
+  fn from(v: glam::Vec3) -> Self {
+    VertexDataValue::Vec3(v.to_array())
+  }
+}
+
+/// element type of an index buffer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexType {
+  U16,
+  U32,
+}
+
+impl From<IndexType> for u32 {
+  fn from(x: IndexType) -> Self {
+    match x {
+      IndexType::U16 => WebGl2RenderingContext::UNSIGNED_SHORT,
+      IndexType::U32 => WebGl2RenderingContext::UNSIGNED_INT,
+    }
+  }
+}
+
+/// vertex indices for `draw_elements`, uploaded as an `ELEMENT_ARRAY_BUFFER`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Indices {
+  U16(Vec<u16>),
+  U32(Vec<u32>),
+}
+
+impl Indices {
+  /// picks `U16` when every index fits. the largest value of each type is never used,
+  /// WebGL2 always reads it as primitive restart, so `u32::MAX` is an error
+  pub fn from_u32(xs: Vec<u32>) -> Result<Self, TriadicaError> {
+    if xs.contains(&u32::MAX) {
+      return Err(TriadicaError::Other(format!(
+        "index {} is reserved for primitive restart",
+        u32::MAX
+      )));
+    }
+    if xs.iter().all(|x| *x < u16::MAX as u32) {
+      Ok(Indices::U16(xs.into_iter().map(|x| x as u16).collect()))
+    } else {
+      Ok(Indices::U32(xs))
+    }
+  }
+
+  /// whether the largest value of the index type is used, which draws read as primitive restart
+  pub fn has_restart_index(&self) -> bool {
+    match self {
+      Indices::U16(xs) => xs.contains(&u16::MAX),
+      Indices::U32(xs) => xs.contains(&u32::MAX),
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  pub fn len(&self) -> usize {
+    match self {
+      Indices::U16(xs) => xs.len(),
+      Indices::U32(xs) => xs.len(),
+    }
+  }
+
+  pub fn index_type(&self) -> IndexType {
+    match self {
+      Indices::U16(_) => IndexType::U16,
+      Indices::U32(_) => IndexType::U32,
+    }
+  }
+
+  pub fn get(&self, idx: usize) -> Option<usize> {
+    match self {
+      Indices::U16(xs) => xs.get(idx).map(|x| *x as usize),
+      Indices::U32(xs) => xs.get(idx).map(|x| *x as usize),
+    }
+  }
+
+  /// largest index, vertices should be more than this
+  pub fn max_index(&self) -> Option<usize> {
+    match self {
+      Indices::U16(xs) => xs.iter().max().map(|x| *x as usize),
+      Indices::U32(xs) => xs.iter().max().map(|x| *x as usize),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn keeps_restart_index_out_of_u16() {
+    assert_eq!(Indices::from_u32(vec![0, 65534]), Ok(Indices::U16(vec![0, 65534])));
+    let indices = Indices::from_u32(vec![0, 65535]).unwrap();
+    assert_eq!(indices, Indices::U32(vec![0, 65535]));
+    assert!(!indices.has_restart_index());
+    assert!(Indices::U16(vec![1, u16::MAX]).has_restart_index());
+
+    assert!(Indices::from_u32(vec![0, u32::MAX]).is_err());
+    assert_eq!(Indices::from_u32(vec![0, u32::MAX - 1]).map(|x| x.index_type()), Ok(IndexType::U32));
+  }
+
+  #[test]
+  fn maps_draw_modes_to_gl() {
+    let modes = [
+      (DrawMode::Points, WebGl2RenderingContext::POINTS),
+      (DrawMode::LineLoop, WebGl2RenderingContext::LINE_LOOP),
+      (DrawMode::TriangleFan, WebGl2RenderingContext::TRIANGLE_FAN),
+      (DrawMode::TriangleStrip, WebGl2RenderingContext::TRIANGLE_STRIP),
+    ];
+    for (mode, gl) in modes {
+      assert_eq!(u32::from(mode), gl, "{mode:?}");
+    }
+  }
 }
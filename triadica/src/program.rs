@@ -1,27 +1,294 @@
 //! this file builds shader program, and it uses caches to skip duplications
 
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+  cell::RefCell,
+  collections::{hash_map::DefaultHasher, HashMap},
+  hash::{Hash, Hasher},
+  rc::Rc,
+};
 
-use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlShader};
+use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlShader, WebGlUniformLocation};
 
-#[derive(Default)]
+use crate::error::{lost_or, ShaderStage, TriadicaError};
+use crate::reflect::{ActiveVariable, ProgramReflection};
+
+/// programs kept by default, including ones no object uses
+pub const DEFAULT_PROGRAM_CAPACITY: usize = 64;
+
+/// hash of a vertex and fragment shader pair, computed once when compiling components
+/// so that looking up programs does not hash sources again. hashes may collide, caches compare sources on hits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProgramKey(u64);
+
+impl ProgramKey {
+  pub fn of(vert_shader: &str, frag_shader: &str) -> Self {
+    let mut hasher = DefaultHasher::new();
+    vert_shader.hash(&mut hasher);
+    frag_shader.hash(&mut hasher);
+    ProgramKey(hasher.finish())
+  }
+}
+
+/// counters of program caches, for diagnostics
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ProgramCacheStats {
+  pub hits: u64,
+  pub misses: u64,
+  /// programs deleted for exceeding capacity
+  pub evictions: u64,
+  /// milliseconds spent compiling and linking, in total
+  pub link_time: f64,
+}
+
+/// a linked program with its active attributes and uniforms, queried once after linking
+/// so binding by name is a map lookup
+pub struct LinkedProgram {
+  pub program: WebGlProgram,
+  pub reflection: ProgramReflection,
+  attrib_locations: HashMap<String, u32>,
+  uniform_locations: HashMap<String, WebGlUniformLocation>,
+}
+
+impl LinkedProgram {
+  fn reflect(context: &WebGl2RenderingContext, program: WebGlProgram) -> Self {
+    let count = |pname: u32| context.get_program_parameter(&program, pname).as_f64().unwrap_or(0.0) as u32;
+    let mut reflection = ProgramReflection::default();
+    let mut attrib_locations = HashMap::new();
+    let mut uniform_locations = HashMap::new();
+
+    for idx in 0..count(WebGl2RenderingContext::ACTIVE_ATTRIBUTES) {
+      let Some(info) = context.get_active_attrib(&program, idx) else {
+        continue;
+      };
+      let name = info.name();
+      let location = context.get_attrib_location(&program, &name);
+      if location >= 0 {
+        attrib_locations.insert(name.to_owned(), location as u32);
+      }
+      let variable = ActiveVariable {
+        gl_type: info.type_(),
+        size: info.size(),
+      };
+      reflection.attributes.insert(name, variable);
+    }
+
+    for idx in 0..count(WebGl2RenderingContext::ACTIVE_UNIFORMS) {
+      let Some(info) = context.get_active_uniform(&program, idx) else {
+        continue;
+      };
+      let name = info.name();
+      // uniforms in blocks have no location
+      if let Some(location) = context.get_uniform_location(&program, &name) {
+        let short = name.strip_suffix("[0]").unwrap_or(&name);
+        uniform_locations.insert(short.to_owned(), location);
+      }
+      let variable = ActiveVariable {
+        gl_type: info.type_(),
+        size: info.size(),
+      };
+      reflection.insert_uniform(&name, variable);
+    }
+
+    Self {
+      program,
+      reflection,
+      attrib_locations,
+      uniform_locations,
+    }
+  }
+
+  /// `None` when the attribute is not active
+  pub fn attrib_location(&self, name: &str) -> Option<u32> {
+    self.attrib_locations.get(name).copied()
+  }
+
+  /// `None` when the uniform is not active
+  pub fn uniform_location(&self, name: &str) -> Option<&WebGlUniformLocation> {
+    self.uniform_locations.get(name)
+  }
+}
+
+struct CachedProgram {
+  program: Rc<LinkedProgram>,
+  /// sources linked from, to tell programs of colliding keys apart
+  vert_shader: String,
+  frag_shader: String,
+  /// objects using the program, it is only evicted at 0
+  users: usize,
+  /// value of the clock when last looked up
+  last_used: u64,
+  /// sources of the key are replaced, so it is deleted at 0 users instead of kept for reuse
+  retired: bool,
+}
+
+/// linked programs by key. programs no object uses are kept for reuse,
+/// and least recently used ones are deleted when there are more than `capacity` programs
 pub struct ShaderProgramCaches {
-  v: HashMap<String, WebGlProgram>,
+  v: HashMap<ProgramKey, CachedProgram>,
+  /// keys of cached programs by address, for releasing without scanning
+  keys: HashMap<*const LinkedProgram, ProgramKey>,
+  pub capacity: usize,
+  /// increased on every lookup
+  clock: u64,
+  stats: ProgramCacheStats,
 }
 
-fn link_program(context: &WebGl2RenderingContext, vert_shader: &str, frag_shader: &str) -> Result<WebGlProgram, String> {
-  let program = context
-    .create_program()
-    .ok_or_else(|| String::from("Unable to create shader object"))?;
+impl Default for ShaderProgramCaches {
+  fn default() -> Self {
+    Self::with_capacity(DEFAULT_PROGRAM_CAPACITY)
+  }
+}
+
+impl ShaderProgramCaches {
+  pub fn with_capacity(capacity: usize) -> Self {
+    Self {
+      v: HashMap::new(),
+      keys: HashMap::new(),
+      capacity,
+      clock: 0,
+      stats: ProgramCacheStats::default(),
+    }
+  }
+
+  /// number of cached programs
+  pub fn len(&self) -> usize {
+    self.v.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.v.is_empty()
+  }
+
+  /// number of cached programs no object uses
+  pub fn idle(&self) -> usize {
+    self.v.values().filter(|entry| entry.users == 0).count()
+  }
+
+  pub fn stats(&self) -> ProgramCacheStats {
+    self.stats
+  }
+
+  /// drop every program without deleting, for lost contexts where programs are invalid
+  pub fn forget(&mut self) {
+    self.v.clear();
+    self.keys.clear();
+  }
+
+  /// a cached program with one more user, `None` when sources of the key differ
+  fn acquire(&mut self, key: ProgramKey, vert_shader: &str, frag_shader: &str) -> Option<Rc<LinkedProgram>> {
+    self.clock += 1;
+    let entry = self.v.get_mut(&key)?;
+    if entry.vert_shader != vert_shader || entry.frag_shader != frag_shader {
+      return None;
+    }
+    entry.users += 1;
+    entry.last_used = self.clock;
+    // linked from these sources again, like after a replacement is undone
+    entry.retired = false;
+    Some(entry.program.clone())
+  }
+
+  /// returns `false` when the key is taken by other sources, then the program is not cached
+  fn insert(&mut self, key: ProgramKey, vert_shader: &str, frag_shader: &str, program: Rc<LinkedProgram>) -> bool {
+    if self.v.contains_key(&key) {
+      return false;
+    }
+    self.keys.insert(Rc::as_ptr(&program), key);
+    let entry = CachedProgram {
+      program,
+      vert_shader: vert_shader.to_owned(),
+      frag_shader: frag_shader.to_owned(),
+      users: 1,
+      last_used: self.clock,
+      retired: false,
+    };
+    self.v.insert(key, entry);
+    true
+  }
+
+  fn entry_of(&mut self, program: &Rc<LinkedProgram>) -> Option<&mut CachedProgram> {
+    let key = self.keys.get(&Rc::as_ptr(program))?;
+    self.v.get_mut(key)
+  }
+
+  /// one user fewer, returns `false` for programs not in caches
+  fn release(&mut self, program: &Rc<LinkedProgram>) -> bool {
+    match self.entry_of(program) {
+      Some(entry) => {
+        entry.users = entry.users.saturating_sub(1);
+        true
+      }
+      None => false,
+    }
+  }
 
-  let vert_shader = compile_shader(context, WebGl2RenderingContext::VERTEX_SHADER, vert_shader)?;
+  /// mark a program to be evicted as soon as no object uses it
+  fn retire(&mut self, program: &Rc<LinkedProgram>) {
+    if let Some(entry) = self.entry_of(program) {
+      entry.retired = true;
+    }
+  }
+
+  fn remove(&mut self, key: &ProgramKey) -> Option<Rc<LinkedProgram>> {
+    let entry = self.v.remove(key)?;
+    self.keys.remove(&Rc::as_ptr(&entry.program));
+    Some(entry.program)
+  }
 
-  let frag_shader = compile_shader(context, WebGl2RenderingContext::FRAGMENT_SHADER, frag_shader)?;
+  /// remove retired programs without users, then least recently used programs without users until within capacity,
+  /// returns programs to delete
+  fn evict(&mut self) -> Vec<Rc<LinkedProgram>> {
+    let retired: Vec<ProgramKey> = self
+      .v
+      .iter()
+      .filter(|(_, entry)| entry.users == 0 && entry.retired)
+      .map(|(key, _)| *key)
+      .collect();
+    let mut evicted: Vec<Rc<LinkedProgram>> = retired.iter().filter_map(|key| self.remove(key)).collect();
+
+    let mut idle: Vec<(u64, ProgramKey)> = self
+      .v
+      .iter()
+      .filter(|(_, entry)| entry.users == 0)
+      .map(|(key, entry)| (entry.last_used, *key))
+      .collect();
+    idle.sort_unstable_by_key(|(last_used, _)| *last_used);
+    let excess = self.v.len().saturating_sub(self.capacity);
+    let excess: Vec<Rc<LinkedProgram>> = idle.into_iter().take(excess).filter_map(|(_, key)| self.remove(&key)).collect();
+    self.stats.evictions += excess.len() as u64;
+    evicted.extend(excess);
+    evicted
+  }
+}
+
+fn link_program(context: &WebGl2RenderingContext, vert_shader: &str, frag_shader: &str) -> Result<WebGlProgram, TriadicaError> {
+  let vert_shader = compile_shader(context, ShaderStage::Vertex, vert_shader)?;
+  let frag_shader = match compile_shader(context, ShaderStage::Fragment, frag_shader) {
+    Ok(shader) => shader,
+    Err(e) => {
+      context.delete_shader(Some(&vert_shader));
+      return Err(e);
+    }
+  };
+  let program = match context.create_program() {
+    Some(program) => program,
+    None => {
+      context.delete_shader(Some(&vert_shader));
+      context.delete_shader(Some(&frag_shader));
+      return Err(lost_or(context, TriadicaError::Other("Unable to create program object".to_owned())));
+    }
+  };
 
   context.attach_shader(&program, &vert_shader);
   context.attach_shader(&program, &frag_shader);
   context.link_program(&program);
 
+  // shaders are not needed after linking, whether it succeeds or not
+  for shader in [&vert_shader, &frag_shader] {
+    context.detach_shader(&program, shader);
+    context.delete_shader(Some(shader));
+  }
+
   if context
     .get_program_parameter(&program, WebGl2RenderingContext::LINK_STATUS)
     .as_bool()
@@ -29,36 +296,69 @@ fn link_program(context: &WebGl2RenderingContext, vert_shader: &str, frag_shader
   {
     Ok(program)
   } else {
-    Err(
-      context
-        .get_program_info_log(&program)
-        .unwrap_or_else(|| String::from("Unknown error creating program object")),
-    )
+    let log = context
+      .get_program_info_log(&program)
+      .unwrap_or_else(|| String::from("Unknown error creating program object"));
+    context.delete_program(Some(&program));
+    Err(TriadicaError::ProgramLink { log })
   }
 }
 
+/// look up a program by key, or link one from sources. each call counts a user until `release_program`
 pub fn cached_link_program(
   context: &WebGl2RenderingContext,
+  key: ProgramKey,
   vert_shader: &str,
   frag_shader: &str,
   caches: Rc<RefCell<ShaderProgramCaches>>,
-) -> Result<WebGlProgram, String> {
-  let key = format!("{vert_shader}\n@@@@\n{frag_shader}");
+) -> Result<Rc<LinkedProgram>, TriadicaError> {
+  let mut p = (*caches).borrow_mut();
+  if let Some(program) = p.acquire(key, vert_shader, frag_shader) {
+    p.stats.hits += 1;
+    return Ok(program);
+  }
+
+  p.stats.misses += 1;
+  let started = now();
+  let linked = link_program(context, vert_shader, frag_shader);
+  p.stats.link_time += now() - started;
+  let program = Rc::new(LinkedProgram::reflect(context, linked?));
+  // a program of a colliding key is not cached, and deleted once released
+  p.insert(key, vert_shader, frag_shader, program.clone());
+  for evicted in p.evict() {
+    context.delete_program(Some(&evicted.program));
+  }
+  Ok(program)
+}
 
+/// a user of the program is gone, unused programs stay cached until evicted.
+/// programs not from caches are deleted at once
+pub fn release_program(context: &WebGl2RenderingContext, program: Rc<LinkedProgram>, caches: Rc<RefCell<ShaderProgramCaches>>) {
   let mut p = (*caches).borrow_mut();
-  if let Some(program) = p.v.get(&key) {
-    Ok(program.clone())
+  if p.release(&program) {
+    for evicted in p.evict() {
+      context.delete_program(Some(&evicted.program));
+    }
   } else {
-    let program = link_program(context, vert_shader, frag_shader)?;
-    p.v.insert(key, program.clone());
-    Ok(program)
+    context.delete_program(Some(&program.program));
   }
 }
 
-fn compile_shader(context: &WebGl2RenderingContext, shader_type: u32, source: &str) -> Result<WebGlShader, String> {
+/// like `release_program`, for a program whose sources are replaced, it is deleted once no object uses it
+pub fn retire_program(context: &WebGl2RenderingContext, program: Rc<LinkedProgram>, caches: Rc<RefCell<ShaderProgramCaches>>) {
+  caches.borrow_mut().retire(&program);
+  release_program(context, program, caches)
+}
+
+/// milliseconds from `performance.now()`, 0 without it
+fn now() -> f64 {
+  web_sys::window().and_then(|w| w.performance()).map_or(0.0, |p| p.now())
+}
+
+fn compile_shader(context: &WebGl2RenderingContext, stage: ShaderStage, source: &str) -> Result<WebGlShader, TriadicaError> {
   let shader = context
-    .create_shader(shader_type)
-    .ok_or_else(|| String::from("Unable to create shader object"))?;
+    .create_shader(stage.into())
+    .ok_or_else(|| lost_or(context, TriadicaError::Other("Unable to create shader object".to_owned())))?;
   context.shader_source(&shader, source);
   context.compile_shader(&shader);
 
@@ -69,10 +369,112 @@ fn compile_shader(context: &WebGl2RenderingContext, shader_type: u32, source: &s
   {
     Ok(shader)
   } else {
-    Err(
-      context
-        .get_shader_info_log(&shader)
-        .unwrap_or_else(|| String::from("Unknown error creating shader")),
-    )
+    let log = context
+      .get_shader_info_log(&shader)
+      .unwrap_or_else(|| String::from("Unknown error creating shader"));
+    context.delete_shader(Some(&shader));
+    Err(TriadicaError::shader_compile(stage, log, source))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use wasm_bindgen::{JsCast, JsValue};
+
+  /// a program without GL behind it, caches only compare handles
+  fn program() -> Rc<LinkedProgram> {
+    Rc::new(LinkedProgram {
+      program: JsValue::NULL.unchecked_into(),
+      reflection: ProgramReflection::default(),
+      attrib_locations: HashMap::new(),
+      uniform_locations: HashMap::new(),
+    })
+  }
+
+  fn key(name: &str) -> ProgramKey {
+    ProgramKey::of(name, "")
+  }
+
+  #[test]
+  fn evicts_least_recently_used_idle_programs() {
+    let mut caches = ShaderProgramCaches::with_capacity(2);
+    let (a, b, c) = (program(), program(), program());
+    caches.insert(key("a"), "a", "", a.clone());
+    caches.insert(key("b"), "b", "", b.clone());
+    assert!(caches.release(&a));
+    assert!(caches.release(&b));
+    // `a` is used again, so `b` is the least recently used
+    assert!(Rc::ptr_eq(&caches.acquire(key("a"), "a", "").unwrap(), &a));
+    assert!(caches.release(&a));
+
+    caches.insert(key("c"), "c", "", c.clone());
+    let evicted = caches.evict();
+    assert_eq!(evicted.len(), 1);
+    assert!(Rc::ptr_eq(&evicted[0], &b));
+    assert!(caches.acquire(key("b"), "b", "").is_none());
+    assert_eq!((caches.len(), caches.idle(), caches.stats().evictions), (2, 1, 1));
+  }
+
+  #[test]
+  fn keeps_programs_in_use_beyond_capacity() {
+    let mut caches = ShaderProgramCaches::with_capacity(1);
+    let (a, b) = (program(), program());
+    caches.insert(key("a"), "a", "", a.clone());
+    caches.insert(key("b"), "b", "", b);
+    assert!(caches.evict().is_empty());
+    assert_eq!(caches.len(), 2);
+
+    assert!(caches.release(&a));
+    assert_eq!(caches.evict().len(), 1);
+    assert!(!caches.release(&program()), "programs not from caches are not found");
+  }
+
+  #[test]
+  fn evicts_retired_programs_once_unused() {
+    let mut caches = ShaderProgramCaches::default();
+    let (a, b) = (program(), program());
+    caches.insert(key("a"), "a", "", a.clone());
+    caches.insert(key("b"), "b", "", b.clone());
+    caches.acquire(key("a"), "a", "");
+    caches.retire(&a);
+    caches.release(&a);
+    assert!(caches.evict().is_empty(), "one user left");
+    caches.release(&a);
+    let evicted = caches.evict();
+    assert_eq!(evicted.len(), 1);
+    assert!(Rc::ptr_eq(&evicted[0], &a));
+    assert_eq!(caches.stats().evictions, 0, "only counted for capacity");
+
+    // linking from same sources again takes it back
+    caches.retire(&b);
+    caches.acquire(key("b"), "b", "");
+    caches.release(&b);
+    caches.release(&b);
+    assert!(caches.evict().is_empty());
+  }
+
+  #[test]
+  fn tells_apart_sources_of_colliding_keys() {
+    let mut caches = ShaderProgramCaches::default();
+    let (a, b) = (program(), program());
+    let collided = ProgramKey(1);
+    assert!(caches.insert(collided, "a", "", a.clone()));
+    assert!(caches.acquire(collided, "b", "").is_none());
+    assert!(!caches.insert(collided, "b", "", b.clone()));
+    assert!(!caches.release(&b), "program of the colliding key is deleted when released");
+    assert!(Rc::ptr_eq(&caches.acquire(collided, "a", "").unwrap(), &a));
+    assert_eq!(caches.len(), 1);
+  }
+
+  #[test]
+  fn forgets_addresses_of_evicted_programs() {
+    let mut caches = ShaderProgramCaches::with_capacity(0);
+    let a = program();
+    caches.insert(key("a"), "a", "", a.clone());
+    assert!(caches.release(&a));
+    assert_eq!(caches.evict().len(), 1);
+    assert!(caches.keys.is_empty());
+    assert!(!caches.release(&a));
   }
 }
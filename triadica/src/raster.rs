@@ -0,0 +1,695 @@
+//! CPU renderer for headless rendering, draws a compiled tree into RGBA pixels.
+//! GLSL can not run here, so vertices go through `Camera::transform_perspective` and colors come from a `FragmentShading`.
+
+use std::{fs, path::Path, rc::Rc};
+
+use glam::{Affine3A, Quat, Vec3};
+
+use crate::component::{ComponentCache, InstanceArrays, TriadicaElementTree};
+use crate::error::TriadicaError;
+use crate::primes::{DrawMode, POINT_SIZE_ATTRIBUTE};
+use crate::viewer::Camera;
+
+/// attribute treated as vertex position
+pub const POSITION_ATTRIBUTE: &str = "a_position";
+
+/// values interpolated for each fragment, like varyings in `demo.vert`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fragment {
+  pub r: f32,
+  pub s: f32,
+  /// window depth in `0..1`
+  pub depth: f32,
+}
+
+/// computes RGBA color of a fragment, channels in `0..1`
+pub type FragmentShading = Rc<dyn Fn(&Fragment) -> [f32; 4]>;
+
+/// same coloring as `demo.frag`
+pub fn demo_shading(frag: &Fragment) -> [f32; 4] {
+  if frag.r >= 0.0 {
+    let factor = smoothstep(0.0, 0.4, 1.0 - frag.r / 10.0);
+    [0.6 + factor, 0.6 + factor, 1.0 - factor, 1.0]
+  } else if frag.r > -frag.s {
+    [0.6, 0.6, 1.0, 1.0]
+  } else {
+    // supposed to be hidden with depth test
+    [0.0, 0.0, 0.0, 1.0]
+  }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+  let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+  t * t * (3.0 - 2.0 * t)
+}
+
+/// vertex after projection, in clip space where `w` is 1
+#[derive(Debug, Clone, Copy)]
+struct ClipVertex {
+  position: Vec3,
+  r: f32,
+  s: f32,
+}
+
+impl ClipVertex {
+  fn lerp(&self, other: &Self, t: f32) -> Self {
+    Self {
+      position: self.position.lerp(other.position, t),
+      r: self.r + (other.r - self.r) * t,
+      s: self.s + (other.s - self.s) * t,
+    }
+  }
+}
+
+/// vertex in window coordinates, `y` grows downward
+#[derive(Debug, Clone, Copy)]
+struct WindowVertex {
+  x: f32,
+  y: f32,
+  depth: f32,
+  r: f32,
+  s: f32,
+}
+
+/// renders triangles, lines and points with depth test, `LESS` like `context_setup`
+pub struct SoftwareRenderer {
+  pub width: usize,
+  pub height: usize,
+  pub clear_color: [f32; 4],
+  pub shading: FragmentShading,
+  colors: Vec<[f32; 4]>,
+  depths: Vec<f32>,
+}
+
+impl SoftwareRenderer {
+  pub fn new(width: usize, height: usize) -> Self {
+    Self {
+      width,
+      height,
+      clear_color: [0.0, 0.0, 0.0, 1.0],
+      shading: Rc::new(demo_shading),
+      colors: vec![[0.0, 0.0, 0.0, 1.0]; width * height],
+      depths: vec![1.0; width * height],
+    }
+  }
+
+  /// clear color and depth
+  pub fn clear(&mut self) {
+    self.colors.fill(self.clear_color);
+    self.depths.fill(1.0);
+  }
+
+  /// clear and draw every object of the tree, objects without `a_position` are skipped
+  pub fn render_tree(&mut self, tree: &TriadicaElementTree, camera: &Camera) -> Result<(), TriadicaError> {
+    self.clear();
+    for item in tree.objects() {
+      self.draw_object(item, camera)?;
+    }
+    Ok(())
+  }
+
+  /// draw one object into current buffers.
+  /// instances are placed by `a_offset`, `a_scale` and `a_rotation` as `Instance` describes
+  pub fn draw_object(&mut self, item: &ComponentCache, camera: &Camera) -> Result<(), TriadicaError> {
+    let positions = match positions_of(item)? {
+      Some(positions) => positions,
+      None => return Ok(()),
+    };
+
+    match &item.instances {
+      Some(instances) => {
+        for idx in 0..instances.count {
+          let transform = instance_transform(instances, idx);
+          let moved: Vec<Vec3> = positions.iter().map(|p| transform.transform_point3(*p)).collect();
+          self.draw_positions(item, &moved, camera)?;
+        }
+        Ok(())
+      }
+      None => self.draw_positions(item, &positions, camera),
+    }
+  }
+
+  fn draw_positions(&mut self, item: &ComponentCache, positions: &[Vec3], camera: &Camera) -> Result<(), TriadicaError> {
+    let vertices: Vec<ClipVertex> = positions
+      .iter()
+      .map(|position| {
+        let p = camera.transform_perspective(*position);
+        ClipVertex {
+          position: p.point * crate::viewer::CLIP_SCALE,
+          r: p.r,
+          s: p.s,
+        }
+      })
+      .collect();
+
+    let order = vertex_order(item, vertices.len())?;
+    let vertices: Vec<ClipVertex> = order.iter().map(|idx| vertices[*idx]).collect();
+
+    match item.draw_mode {
+      DrawMode::Points => {
+        let sizes = point_sizes(item);
+        for (v, idx) in vertices.iter().zip(&order) {
+          let size = sizes.and_then(|xs| xs.get(*idx)).copied().unwrap_or(item.point_size);
+          self.draw_point(v, size);
+        }
+      }
+      DrawMode::Lines => {
+        for pair in vertices.chunks_exact(2) {
+          self.draw_line(&pair[0], &pair[1]);
+        }
+      }
+      DrawMode::LineStrip => {
+        for pair in vertices.windows(2) {
+          self.draw_line(&pair[0], &pair[1]);
+        }
+      }
+      DrawMode::LineLoop => {
+        for pair in vertices.windows(2) {
+          self.draw_line(&pair[0], &pair[1]);
+        }
+        if let [first, .., last] = vertices.as_slice() {
+          self.draw_line(last, first);
+        }
+      }
+      DrawMode::Triangles => {
+        for tri in vertices.chunks_exact(3) {
+          self.draw_triangle(&tri[0], &tri[1], &tri[2]);
+        }
+      }
+      DrawMode::TriangleStrip => {
+        for tri in vertices.windows(3) {
+          self.draw_triangle(&tri[0], &tri[1], &tri[2]);
+        }
+      }
+      DrawMode::TriangleFan => {
+        for pair in vertices.windows(2).skip(1) {
+          self.draw_triangle(&vertices[0], &pair[0], &pair[1]);
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// copy pixels out, colors are clamped to `0..1`
+  pub fn to_image(&self) -> RgbaImage {
+    let mut data = Vec::with_capacity(self.width * self.height * 4);
+    for color in &self.colors {
+      for channel in color {
+        data.push((channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+      }
+    }
+    RgbaImage {
+      width: self.width,
+      height: self.height,
+      data,
+    }
+  }
+
+  fn to_window(&self, v: &ClipVertex) -> WindowVertex {
+    WindowVertex {
+      x: (v.position.x + 1.0) * 0.5 * self.width as f32,
+      y: (1.0 - v.position.y) * 0.5 * self.height as f32,
+      depth: (v.position.z + 1.0) * 0.5,
+      r: v.r,
+      s: v.s,
+    }
+  }
+
+  fn plot(&mut self, x: i64, y: i64, depth: f32, r: f32, s: f32) {
+    if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+      return;
+    }
+    let idx = y as usize * self.width + x as usize;
+    if depth < self.depths[idx] {
+      self.depths[idx] = depth;
+      self.colors[idx] = (self.shading)(&Fragment { r, s, depth });
+    }
+  }
+
+  /// square of `size` pixels centered at the vertex, dropped when out of depth range like GL does
+  fn draw_point(&mut self, v: &ClipVertex, size: f32) {
+    if !(-1.0..=1.0).contains(&v.position.z) {
+      return;
+    }
+    let w = self.to_window(v);
+    let size = size.round().max(1.0);
+    let x0 = (w.x - size * 0.5).round() as i64;
+    let y0 = (w.y - size * 0.5).round() as i64;
+    for y in y0..(y0 + size as i64) {
+      for x in x0..(x0 + size as i64) {
+        self.plot(x, y, w.depth, w.r, w.s);
+      }
+    }
+  }
+
+  fn draw_line(&mut self, a: &ClipVertex, b: &ClipVertex) {
+    let (a, b) = match clip_line(a, b) {
+      Some(pair) => pair,
+      None => return,
+    };
+    let a = self.to_window(&a);
+    let b = self.to_window(&b);
+    let steps = (b.x - a.x).abs().max((b.y - a.y).abs()).ceil().max(1.0) as usize;
+    for i in 0..=steps {
+      let t = i as f32 / steps as f32;
+      let x = a.x + (b.x - a.x) * t;
+      let y = a.y + (b.y - a.y) * t;
+      self.plot(
+        x.floor() as i64,
+        y.floor() as i64,
+        a.depth + (b.depth - a.depth) * t,
+        a.r + (b.r - a.r) * t,
+        a.s + (b.s - a.s) * t,
+      );
+    }
+  }
+
+  fn draw_triangle(&mut self, a: &ClipVertex, b: &ClipVertex, c: &ClipVertex) {
+    let polygon = clip_polygon(vec![*a, *b, *c]);
+    if polygon.len() < 3 {
+      return;
+    }
+    let polygon: Vec<WindowVertex> = polygon.iter().map(|v| self.to_window(v)).collect();
+    for idx in 1..(polygon.len() - 1) {
+      self.fill_triangle(&polygon[0], &polygon[idx], &polygon[idx + 1]);
+    }
+  }
+
+  fn fill_triangle(&mut self, a: &WindowVertex, b: &WindowVertex, c: &WindowVertex) {
+    let area = edge(a, b, c.x, c.y);
+    if area.abs() < f32::EPSILON {
+      return;
+    }
+    let min_x = a.x.min(b.x).min(c.x).floor().max(0.0) as i64;
+    let max_x = a.x.max(b.x).max(c.x).ceil().min(self.width as f32) as i64;
+    let min_y = a.y.min(b.y).min(c.y).floor().max(0.0) as i64;
+    let max_y = a.y.max(b.y).max(c.y).ceil().min(self.height as f32) as i64;
+    for y in min_y..max_y {
+      for x in min_x..max_x {
+        let px = x as f32 + 0.5;
+        let py = y as f32 + 0.5;
+        let wa = edge(b, c, px, py) / area;
+        let wb = edge(c, a, px, py) / area;
+        let wc = edge(a, b, px, py) / area;
+        if wa < 0.0 || wb < 0.0 || wc < 0.0 {
+          continue;
+        }
+        self.plot(
+          x,
+          y,
+          a.depth * wa + b.depth * wb + c.depth * wc,
+          a.r * wa + b.r * wb + c.r * wc,
+          a.s * wa + b.s * wb + c.s * wc,
+        );
+      }
+    }
+  }
+}
+
+/// positions from `a_position`, `None` when the object has no such attribute
+pub(crate) fn positions_of(item: &ComponentCache) -> Result<Option<Vec<Vec3>>, TriadicaError> {
+  let idx = match item.attr_names.iter().position(|(name, _)| name == POSITION_ATTRIBUTE) {
+    Some(idx) => idx,
+    None => return Ok(None),
+  };
+  let unit_size = item.attr_names[idx].1 as usize;
+  let data = item
+    .arrays
+    .get(idx)
+    .ok_or_else(|| TriadicaError::AttributeMismatch(format!("missing data for {POSITION_ATTRIBUTE}")))?;
+  if unit_size < 3 || data.len() < item.size * unit_size {
+    return Err(TriadicaError::AttributeMismatch(format!(
+      "expected {} floats of vec3 positions, got {}",
+      item.size * unit_size,
+      data.len()
+    )));
+  }
+  Ok(Some(
+    data
+      .chunks(unit_size)
+      .take(item.size)
+      .map(|chunk| Vec3::new(chunk[0], chunk[1], chunk[2]))
+      .collect(),
+  ))
+}
+
+/// indices of vertices in the order drawn, expanding indexed geometry the same way `draw_elements` reads it
+pub(crate) fn vertex_order(item: &ComponentCache, count: usize) -> Result<Vec<usize>, TriadicaError> {
+  let order: Vec<usize> = match &item.indices {
+    Some(indices) => (0..indices.len()).map(|i| indices.get(i).expect("index in range")).collect(),
+    None => (0..count).collect(),
+  };
+  if let Some(idx) = order.iter().find(|idx| **idx >= count) {
+    return Err(TriadicaError::Other(format!("index {idx} out of range")));
+  }
+  Ok(order)
+}
+
+/// per-vertex sizes from `a_point_size`, `None` when the object has no such attribute
+pub(crate) fn point_sizes(item: &ComponentCache) -> Option<&Vec<f32>> {
+  let idx = item
+    .attr_names
+    .iter()
+    .position(|(name, size)| name == POINT_SIZE_ATTRIBUTE && *size == 1)?;
+  item.arrays.get(idx)
+}
+
+/// transform of an instance from `a_offset`, `a_scale` and `a_rotation` following `Instance`, missing ones are identity
+pub(crate) fn instance_transform(instances: &InstanceArrays, idx: usize) -> Affine3A {
+  let read = |name: &str, size: usize| -> Option<&[f32]> {
+    let pos = instances.attr_names.iter().position(|(n, s)| n == name && *s as usize == size)?;
+    instances.arrays.get(pos)?.get(idx * size..(idx + 1) * size)
+  };
+  let offset = read("a_offset", 3).map_or(Vec3::ZERO, Vec3::from_slice);
+  let scale = read("a_scale", 1).map_or(1.0, |x| x[0]);
+  let rotation = read("a_rotation", 4).map_or(Quat::IDENTITY, |q| Quat::from_slice(q).normalize());
+  Affine3A::from_scale_rotation_translation(Vec3::splat(scale), rotation, offset)
+}
+
+fn edge(a: &WindowVertex, b: &WindowVertex, x: f32, y: f32) -> f32 {
+  (b.x - a.x) * (y - a.y) - (b.y - a.y) * (x - a.x)
+}
+
+/// clip a segment to the `-1..1` cube, so that lines with vertices projected far away near the apex stay short
+fn clip_line(a: &ClipVertex, b: &ClipVertex) -> Option<(ClipVertex, ClipVertex)> {
+  if !a.position.is_finite() || !b.position.is_finite() {
+    return None;
+  }
+  let (mut t0, mut t1) = (0.0_f32, 1.0_f32);
+  let delta = b.position - a.position;
+  for axis in 0..3 {
+    let (d, start) = (delta[axis], a.position[axis]);
+    for (p, q) in [(-d, start + 1.0), (d, 1.0 - start)] {
+      if p.abs() < f32::EPSILON {
+        if q < 0.0 {
+          return None;
+        }
+      } else {
+        let t = q / p;
+        if p < 0.0 {
+          t0 = t0.max(t);
+        } else {
+          t1 = t1.min(t);
+        }
+      }
+    }
+  }
+  if t0 > t1 {
+    None
+  } else {
+    Some((a.lerp(b, t0), a.lerp(b, t1)))
+  }
+}
+
+/// clip a convex polygon to `-1 <= z <= 1`
+fn clip_polygon(polygon: Vec<ClipVertex>) -> Vec<ClipVertex> {
+  let mut result = polygon;
+  for sign in [1.0_f32, -1.0] {
+    // inside when `sign * z <= 1`
+    let distance = |v: &ClipVertex| 1.0 - sign * v.position.z;
+    let input = std::mem::take(&mut result);
+    for (idx, current) in input.iter().enumerate() {
+      let next = &input[(idx + 1) % input.len()];
+      let (dc, dn) = (distance(current), distance(next));
+      if dc >= 0.0 {
+        result.push(*current);
+      }
+      if (dc >= 0.0) != (dn >= 0.0) {
+        result.push(current.lerp(next, dc / (dc - dn)));
+      }
+    }
+  }
+  result
+}
+
+/// 8-bit RGBA pixels, rows from top to bottom
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaImage {
+  pub width: usize,
+  pub height: usize,
+  pub data: Vec<u8>,
+}
+
+impl RgbaImage {
+  /// binary PPM, alpha channel is dropped
+  pub fn to_ppm(&self) -> Vec<u8> {
+    let mut bytes = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+    for pixel in self.data.chunks_exact(4) {
+      bytes.extend_from_slice(&pixel[0..3]);
+    }
+    bytes
+  }
+
+  #[cfg(not(target_arch = "wasm32"))]
+  pub fn to_png(&self) -> Result<Vec<u8>, TriadicaError> {
+    let mut bytes = Vec::new();
+    {
+      let mut encoder = png::Encoder::new(&mut bytes, self.width as u32, self.height as u32);
+      encoder.set_color(png::ColorType::Rgba);
+      encoder.set_depth(png::BitDepth::Eight);
+      let mut writer = encoder
+        .write_header()
+        .map_err(|e| TriadicaError::Image(format!("failed to write png header: {e}")))?;
+      writer
+        .write_image_data(&self.data)
+        .map_err(|e| TriadicaError::Image(format!("failed to write png data: {e}")))?;
+    }
+    Ok(bytes)
+  }
+
+  /// decode an 8-bit RGBA or RGB PNG, for comparing with goldens
+  #[cfg(not(target_arch = "wasm32"))]
+  pub fn from_png(bytes: &[u8]) -> Result<Self, TriadicaError> {
+    let decoder = png::Decoder::new(bytes);
+    let mut reader = decoder
+      .read_info()
+      .map_err(|e| TriadicaError::Image(format!("failed to read png: {e}")))?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader
+      .next_frame(&mut buf)
+      .map_err(|e| TriadicaError::Image(format!("failed to decode png: {e}")))?;
+    let data = match (info.color_type, info.bit_depth) {
+      (png::ColorType::Rgba, png::BitDepth::Eight) => buf[..info.buffer_size()].to_vec(),
+      (png::ColorType::Rgb, png::BitDepth::Eight) => buf[..info.buffer_size()]
+        .chunks_exact(3)
+        .flat_map(|p| [p[0], p[1], p[2], 255])
+        .collect(),
+      (color, depth) => return Err(TriadicaError::Image(format!("unsupported png format: {color:?} {depth:?}"))),
+    };
+    Ok(Self {
+      width: info.width as usize,
+      height: info.height as usize,
+      data,
+    })
+  }
+
+  pub fn write_ppm<P: AsRef<Path>>(&self, path: P) -> Result<(), TriadicaError> {
+    fs::write(path.as_ref(), self.to_ppm()).map_err(|e| TriadicaError::Image(format!("failed to write {:?}: {e}", path.as_ref())))
+  }
+
+  #[cfg(not(target_arch = "wasm32"))]
+  pub fn write_png<P: AsRef<Path>>(&self, path: P) -> Result<(), TriadicaError> {
+    fs::write(path.as_ref(), self.to_png()?).map_err(|e| TriadicaError::Image(format!("failed to write {:?}: {e}", path.as_ref())))
+  }
+
+  #[cfg(not(target_arch = "wasm32"))]
+  pub fn read_png<P: AsRef<Path>>(path: P) -> Result<Self, TriadicaError> {
+    let bytes = fs::read(path.as_ref()).map_err(|e| TriadicaError::Image(format!("failed to read {:?}: {e}", path.as_ref())))?;
+    Self::from_png(&bytes)
+  }
+
+  /// count pixels with any channel differing more than `tolerance`, sizes must match
+  pub fn diff_pixels(&self, other: &Self, tolerance: u8) -> Result<usize, TriadicaError> {
+    if self.width != other.width || self.height != other.height {
+      return Err(TriadicaError::Image(format!(
+        "image sizes differ: {}x{} and {}x{}",
+        self.width, self.height, other.width, other.height
+      )));
+    }
+    Ok(
+      self
+        .data
+        .chunks_exact(4)
+        .zip(other.data.chunks_exact(4))
+        .filter(|(a, b)| a.iter().zip(b.iter()).any(|(x, y)| x.abs_diff(*y) > tolerance))
+        .count(),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alias::{object, points_object};
+  use crate::component::{PackedAttrs, TriadicaElement};
+  use crate::primes::VertexDataValue;
+  use crate::uniform::UniformMap;
+
+  const VERTEX: &str = "#version 300 es
+in vec3 a_position;
+void main() {
+  gl_Position = vec4(a_position, 1.0);
+}
+";
+
+  const FRAGMENT: &str = "#version 300 es
+precision mediump float;
+out vec4 color;
+void main() {
+  color = vec4(1.0);
+}
+";
+
+  /// at `z = 600` facing the origin, 100 units at `z = 0` are 10 pixels from the center
+  fn camera() -> Camera {
+    Camera::looking_at(Vec3::new(0.0, 0.0, 600.0), Vec3::ZERO, Vec3::Y)
+  }
+
+  /// 100x100 pixels, red channel is `r` of fragments, which is 1 at `z = 0`
+  fn canvas() -> SoftwareRenderer {
+    let mut renderer = SoftwareRenderer::new(100, 100);
+    renderer.shading = Rc::new(|frag: &Fragment| [frag.r, 1.0, 1.0, 1.0]);
+    renderer.clear();
+    renderer
+  }
+
+  fn shape(draw_mode: DrawMode, points: &[[f32; 3]]) -> TriadicaElement {
+    object(
+      draw_mode,
+      VERTEX.to_owned(),
+      FRAGMENT.to_owned(),
+      vec![("a_position".to_owned(), 3)],
+      PackedAttrs::List(points.iter().map(|p| PackedAttrs::Item(vec![VertexDataValue::Vec3(*p)])).collect()),
+      Rc::new(UniformMap::default),
+    )
+  }
+
+  fn draw(renderer: &mut SoftwareRenderer, element: TriadicaElement) {
+    let tree = element.compile_to_tree().unwrap();
+    for item in tree.objects() {
+      renderer.draw_object(item, &camera()).unwrap();
+    }
+  }
+
+  fn lit(renderer: &SoftwareRenderer, x: usize, y: usize) -> bool {
+    renderer.colors[y * renderer.width + x][1] > 0.0
+  }
+
+  fn lit_count(renderer: &SoftwareRenderer) -> usize {
+    renderer.colors.iter().filter(|color| color[1] > 0.0).count()
+  }
+
+  #[test]
+  fn fills_triangles_with_depth_test() {
+    let mut renderer = canvas();
+    let triangle = |z: f32| shape(DrawMode::Triangles, &[[-100.0, -100.0, z], [100.0, -100.0, z], [0.0, 100.0, z]]);
+    draw(&mut renderer, triangle(100.0));
+    draw(&mut renderer, triangle(0.0));
+    assert!(lit(&renderer, 50, 50));
+    assert!(!lit(&renderer, 5, 5));
+    // bottom corners are 10 pixels aside at `z = 0`, more for the nearer one
+    assert!(lit(&renderer, 41, 59) && lit(&renderer, 58, 59));
+    assert!(!lit(&renderer, 50, 35));
+
+    // nearer triangle is kept though drawn first, `r` is `(600 - z) / 600`
+    let red = renderer.colors[50 * 100 + 50][0];
+    assert!((red - 5.0 / 6.0).abs() < 1e-3, "{red}");
+  }
+
+  #[test]
+  fn draws_lines_pixel_by_pixel() {
+    let mut renderer = canvas();
+    draw(&mut renderer, shape(DrawMode::Lines, &[[-100.0, 0.0, 0.0], [100.0, 0.0, 0.0]]));
+    let row: Vec<usize> = (0..100).filter(|x| lit(&renderer, *x, 50)).collect();
+    assert_eq!((row.first(), row.last()), (Some(&40), Some(&60)));
+    assert_eq!(lit_count(&renderer), row.len());
+  }
+
+  #[test]
+  fn clips_lines_reaching_far_off_screen() {
+    let camera = camera();
+    // close to the apex of the cone, where projected `x` is far larger than the screen
+    let z = 600.0 + 0.85 * camera.cone_back_scale * camera.look_distance;
+    let mut renderer = canvas();
+    draw(&mut renderer, shape(DrawMode::Lines, &[[0.0, 0.0, 0.0], [-1.0e12, 0.0, z]]));
+    assert!(lit(&renderer, 50, 50));
+    assert!(lit_count(&renderer) <= 51, "one pixel per column toward the right edge");
+    assert!(lit(&renderer, 99, 50));
+  }
+
+  #[test]
+  fn draws_points_as_squares() {
+    let mut renderer = canvas();
+    draw(&mut renderer, shape(DrawMode::Points, &[[0.0, 0.0, 0.0], [-100.0, 0.0, 0.0]]));
+    assert_eq!(lit_count(&renderer), 2);
+    assert!(lit(&renderer, 50, 50) && lit(&renderer, 60, 50));
+
+    let mut sized = canvas();
+    draw(&mut sized, shape(DrawMode::Points, &[[0.0, 0.0, 0.0]]).with_point_size(4.0));
+    assert_eq!(lit_count(&sized), 16);
+    assert!(lit(&sized, 48, 48) && lit(&sized, 51, 51));
+  }
+
+  #[test]
+  fn closes_line_loops() {
+    let square = [
+      [-100.0, -100.0, 0.0],
+      [100.0, -100.0, 0.0],
+      [100.0, 100.0, 0.0],
+      [-100.0, 100.0, 0.0],
+    ];
+    let mut strip = canvas();
+    draw(&mut strip, shape(DrawMode::LineStrip, &square));
+    let mut ring = canvas();
+    draw(&mut ring, shape(DrawMode::LineLoop, &square));
+    // `+x` of world is to the left, so the last vertex is on the right
+    let closing = (41..=59).filter(|y| lit(&ring, 60, *y)).count();
+    assert_eq!(closing, 19);
+    assert!(!lit(&strip, 60, 50));
+    assert!(lit(&strip, 40, 50) && lit(&ring, 40, 50));
+  }
+
+  #[test]
+  fn fans_triangles_around_first_vertex() {
+    // a square as two triangles sharing the first corner
+    let square = [
+      [0.0, 0.0, 0.0],
+      [-100.0, -100.0, 0.0],
+      [100.0, -100.0, 0.0],
+      [100.0, 100.0, 0.0],
+      [-100.0, 100.0, 0.0],
+    ];
+    let mut fan = canvas();
+    draw(&mut fan, shape(DrawMode::TriangleFan, &square));
+    assert!(lit(&fan, 50, 55) && lit(&fan, 45, 50) && lit(&fan, 50, 45));
+    assert!(!lit(&fan, 55, 50), "the side from the last vertex back to the second is open");
+    assert!(!lit(&fan, 35, 50));
+  }
+
+  #[test]
+  fn sizes_points_per_vertex_or_by_object() {
+    let vertex = "#version 300 es
+in vec3 a_position;
+in float a_point_size;
+void main() {
+  gl_PointSize = a_point_size;
+  gl_Position = vec4(a_position, 1.0);
+}
+";
+    let point = |x: f32, size: f32| PackedAttrs::Item(vec![VertexDataValue::Vec3([x, 0.0, 0.0]), VertexDataValue::Float(size)]);
+    let mut renderer = canvas();
+    draw(
+      &mut renderer,
+      points_object(
+        vertex.to_owned(),
+        FRAGMENT.to_owned(),
+        vec![("a_position".to_owned(), 3), ("a_point_size".to_owned(), 1)],
+        PackedAttrs::List(vec![point(0.0, 1.0), point(-300.0, 3.0)]),
+        5.0,
+        Rc::new(UniformMap::default),
+      ),
+    );
+    assert_eq!(lit_count(&renderer), 1 + 9);
+    assert!(lit(&renderer, 50, 50) && lit(&renderer, 79, 49) && lit(&renderer, 81, 51));
+  }
+}
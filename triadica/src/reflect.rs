@@ -0,0 +1,73 @@
+//! active attributes and uniforms of linked programs, for checking data against shaders
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+use crate::component::ObjectId;
+
+/// type and array size of an active attribute or uniform, `gl_type` is a GL enum like `FLOAT_VEC3`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActiveVariable {
+  pub gl_type: u32,
+  pub size: i32,
+}
+
+/// attributes and uniforms a program reads, by name. arrays are named without `[0]`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProgramReflection {
+  pub attributes: BTreeMap<String, ActiveVariable>,
+  pub uniforms: BTreeMap<String, ActiveVariable>,
+}
+
+impl ProgramReflection {
+  /// add an active uniform, array uniforms are reported as `name[0]` by GL
+  pub fn insert_uniform(&mut self, name: &str, variable: ActiveVariable) {
+    let name = name.strip_suffix("[0]").unwrap_or(name);
+    self.uniforms.insert(name.to_owned(), variable);
+  }
+
+  /// attributes the program reads but not in `supplied`, built-in `gl_` inputs are skipped
+  pub fn missing_attributes(&self, supplied: &[&str]) -> Vec<String> {
+    self
+      .attributes
+      .keys()
+      .filter(|name| !name.starts_with("gl_") && !supplied.contains(&name.as_str()))
+      .cloned()
+      .collect()
+  }
+
+  pub fn has_attribute(&self, name: &str) -> bool {
+    self.attributes.contains_key(name)
+  }
+
+  pub fn has_uniform(&self, name: &str) -> bool {
+    self.uniforms.contains_key(name)
+  }
+}
+
+/// data not matching a program, drawing goes on but likely shows something unexpected
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ProgramWarning {
+  /// the vertex shader reads an attribute the object has no data for, it reads a constant instead
+  MissingAttribute { object: ObjectId, name: String },
+  /// the object has data for an attribute not active in the program, not declared or optimized out, the data is skipped
+  InactiveAttribute { object: ObjectId, name: String },
+  /// a uniform is set but not active in the program, not declared or optimized out
+  UnusedUniform { object: ObjectId, name: String },
+}
+
+impl Display for ProgramWarning {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ProgramWarning::MissingAttribute { object, name } => {
+        write!(f, "object {object:?} has no data for attribute `{name}` read by its vertex shader")
+      }
+      ProgramWarning::InactiveAttribute { object, name } => {
+        write!(f, "object {object:?} has data for attribute `{name}`, which its program ignores")
+      }
+      ProgramWarning::UnusedUniform { object, name } => {
+        write!(f, "object {object:?} sets uniform `{name}`, which its program ignores")
+      }
+    }
+  }
+}
@@ -0,0 +1,1567 @@
+//! paints compiled trees with a backend, and keeps GPU resources of objects across frames
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+use glam::{Vec2, Vec3};
+
+use crate::backend::RenderBackend;
+use crate::component::{ComponentCache, ObjectId, TriadicaElementTree};
+use crate::dialect::ShaderUpgrade;
+use crate::error::TriadicaError;
+use crate::pick::{
+  decode_pick_id, encode_pick_id, nearest_vertex, pick_object, PickHit, DEFAULT_PICK_TOLERANCE, PICK_FRAGMENT_SHADER, PICK_ID_UNIFORM,
+  PICK_TARGET,
+};
+use crate::post::{RenderTargetSpec, ScreenPass};
+use crate::primes::{DrawMode, POINT_SIZE_UNIFORM};
+use crate::program::ProgramKey;
+use crate::raster::POSITION_ATTRIBUTE;
+use crate::reflect::{ProgramReflection, ProgramWarning};
+use crate::state::{BlendMode, RenderState};
+use crate::stereo::{StereoOptions, StereoOutput};
+use crate::texture::TextureId;
+use crate::uniform::UniformValue;
+use crate::viewer::{self, Camera};
+use crate::viewport::{Viewport, ViewportRect};
+
+/// GPU resources owned by one compiled object, created once at upload
+struct UploadedObject<B: RenderBackend> {
+  program: B::Program,
+  vertex_array: B::VertexArray,
+  buffers: Vec<B::Buffer>,
+  /// pointed by `buffers` in same order, kept for linking again
+  attributes: Vec<Attribute>,
+  index_buffer: Option<B::Buffer>,
+  /// average position, for sorting transparent objects
+  center: Vec3,
+  /// sources of the object before replacing, to find objects using a shader
+  vertex_shader: String,
+  fragment_shader: String,
+  /// key of the sources above
+  program_key: ProgramKey,
+  /// program writing IDs and a vertex array for it, created at first `pick_by_id`
+  pick: Option<(B::Program, B::VertexArray)>,
+  /// textures the object binds, a texture is deleted with trees when no uploaded object uses it
+  textures: Vec<TextureId>,
+}
+
+/// name, floats per vertex, and divisor of an attribute
+type Attribute = (String, i32, u32);
+
+/// holds a backend and the objects uploaded to it
+pub struct Renderer<B: RenderBackend> {
+  pub backend: B,
+  objects: HashMap<ObjectId, UploadedObject<B>>,
+  /// textures are shared among objects, so kept separately
+  textures: HashMap<TextureId, B::GpuTexture>,
+  /// textures uploaded before their images loaded, uploaded again once loaded
+  pending_textures: HashSet<TextureId>,
+  targets: HashMap<String, TargetSlot<B>>,
+  /// last state sent to the backend, `None` before first draw since it is unknown
+  render_state: Option<RenderState>,
+  /// conversion of GLSL ES 1.00 shaders before linking
+  pub shader_upgrade: ShaderUpgrade,
+  /// replacements of shader sources, by source they replace
+  shader_overrides: HashMap<String, String>,
+  warnings: Warnings,
+  /// between `context_lost` and `context_restored`, painting is skipped
+  context_lost: bool,
+  /// skip objects of trees whose bounds are out of view, on by default
+  pub culling: bool,
+  stats: DrawStats,
+}
+
+/// objects of trees drawn and skipped by culling, counted since the renderer is created
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DrawStats {
+  pub draws: u64,
+  pub culled: u64,
+}
+
+/// warnings found when uploading and drawing, each one is reported once instead of every frame
+#[derive(Default)]
+struct Warnings {
+  pending: Vec<ProgramWarning>,
+  reported: HashSet<ProgramWarning>,
+}
+
+impl Warnings {
+  fn report(&mut self, warning: ProgramWarning) {
+    if self.reported.insert(warning.clone()) {
+      self.pending.push(warning);
+    }
+  }
+
+  /// warn about attributes the program reads and the object does not supply,
+  /// and attributes supplied that the program does not read, which are not pointed
+  fn check_attributes(&mut self, id: ObjectId, reflection: &ProgramReflection, attributes: &[Attribute]) {
+    let supplied: Vec<&str> = attributes.iter().map(|(name, _, _)| name.as_str()).collect();
+    for name in reflection.missing_attributes(&supplied) {
+      self.report(ProgramWarning::MissingAttribute { object: id, name });
+    }
+    for name in supplied {
+      if !reflection.has_attribute(name) {
+        self.report(ProgramWarning::InactiveAttribute {
+          object: id,
+          name: name.to_owned(),
+        });
+      }
+    }
+  }
+}
+
+/// a declared render target, allocated lazily with the size it was created for
+struct TargetSlot<B: RenderBackend> {
+  spec: RenderTargetSpec,
+  allocated: Option<(B::RenderTarget, (i32, i32))>,
+}
+
+impl<B: RenderBackend> Renderer<B> {
+  pub fn new(backend: B) -> Self {
+    Self {
+      backend,
+      objects: HashMap::new(),
+      textures: HashMap::new(),
+      pending_textures: HashSet::new(),
+      targets: HashMap::new(),
+      render_state: None,
+      shader_upgrade: ShaderUpgrade::default(),
+      shader_overrides: HashMap::new(),
+      warnings: Warnings::default(),
+      context_lost: false,
+      culling: true,
+      stats: DrawStats::default(),
+    }
+  }
+
+  pub fn draw_stats(&self) -> DrawStats {
+    self.stats
+  }
+
+  /// number of objects holding GPU resources
+  pub fn uploaded_count(&self) -> usize {
+    self.objects.len()
+  }
+
+  /// upload objects of the tree that are not uploaded yet
+  pub fn upload_tree(&mut self, tree: &TriadicaElementTree) -> Result<(), TriadicaError> {
+    if self.context_lost {
+      return Ok(());
+    }
+    for item in tree.objects() {
+      self.upload_object(item)?;
+    }
+    Ok(())
+  }
+
+  /// link program, create buffers and a vertex array for the object, skipped when already uploaded or empty
+  pub fn upload_object(&mut self, item: &ComponentCache) -> Result<(), TriadicaError> {
+    if self.objects.contains_key(&item.id) || item.is_empty() {
+      return Ok(());
+    }
+    if item.arrays.len() != item.attr_names.len() {
+      return Err(TriadicaError::AttributeMismatch(format!(
+        "arrays and attr_names should have same length: {:?}",
+        item.attr_names
+      )));
+    }
+    if item.indices.as_ref().is_some_and(|xs| xs.has_restart_index()) {
+      return Err(TriadicaError::Other(
+        "largest value of the index type is read as primitive restart".to_owned(),
+      ));
+    }
+    if let Some(max) = item.indices.as_ref().and_then(|xs| xs.max_index()) {
+      if max >= item.size {
+        return Err(TriadicaError::Other(format!("index {max} out of range of {} vertices", item.size)));
+      }
+    }
+
+    // textures go first, they are tracked by id, so a failure leaves nothing of the object behind
+    self.upload_textures(item)?;
+
+    let (key, vertex_shader, fragment_shader) = sources_to_link(
+      &self.shader_overrides,
+      self.shader_upgrade,
+      item.program_key,
+      &item.vertex_shader,
+      &item.fragment_shader,
+    );
+    let backend = &mut self.backend;
+    let program = backend.create_program(key, &vertex_shader, &fragment_shader)?;
+    let vertex_array = match backend.create_vertex_array() {
+      Ok(vertex_array) => vertex_array,
+      Err(e) => {
+        backend.release_program(program);
+        return Err(e);
+      }
+    };
+    backend.bind_vertex_array(Some(&vertex_array));
+
+    let mut buffers = Vec::with_capacity(item.arrays.len());
+    let mut index_buffer = None;
+    let attributes = attributes_of(item);
+    let bound = create_buffers(backend, item, &mut buffers)
+      .and_then(|_| point_attributes(backend, &program, &attributes, &buffers))
+      .and_then(|_| {
+        if let Some(indices) = &item.indices {
+          index_buffer = Some(backend.create_index_buffer(indices)?);
+        }
+        Ok(())
+      });
+    backend.bind_vertex_array(None);
+    if let Err(e) = bound {
+      // release what is created before failing
+      backend.release_program(program);
+      backend.delete_vertex_array(vertex_array);
+      for buffer in buffers.into_iter().chain(index_buffer) {
+        backend.delete_buffer(buffer);
+      }
+      return Err(e);
+    }
+
+    self.warnings.check_attributes(item.id, backend.reflection(&program), &attributes);
+
+    self.objects.insert(
+      item.id,
+      UploadedObject {
+        program,
+        vertex_array,
+        buffers,
+        attributes,
+        index_buffer,
+        center: center_of(item),
+        vertex_shader: item.vertex_shader.to_owned(),
+        fragment_shader: item.fragment_shader.to_owned(),
+        program_key: item.program_key,
+        pick: None,
+        textures: item.textures.iter().map(|(_, texture)| texture.id).collect(),
+      },
+    );
+    Ok(())
+  }
+
+  /// upload textures of the object missing on GPU, including ones disposed while the object still uses them,
+  /// and images that loaded after uploading
+  fn upload_textures(&mut self, item: &ComponentCache) -> Result<(), TriadicaError> {
+    for (_, texture) in &item.textures {
+      let ready = texture.source.is_ready();
+      if ready && self.pending_textures.contains(&texture.id) {
+        self.dispose_texture(texture.id);
+      }
+      if !self.textures.contains_key(&texture.id) {
+        let handle = self.backend.create_texture(texture)?;
+        self.textures.insert(texture.id, handle);
+        if !ready {
+          self.pending_textures.insert(texture.id);
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// swap a shader source at runtime, objects using `original` as vertex or fragment shader are linked again,
+  /// and objects uploaded later use `replacement` as well. returns number of objects linked again.
+  /// when linking fails, every object keeps its last good program and the error is returned
+  pub fn replace_shader(&mut self, original: &str, replacement: &str) -> Result<usize, TriadicaError> {
+    let previous = self.shader_overrides.insert(original.to_owned(), replacement.to_owned());
+    let affected: Vec<ObjectId> = self
+      .objects
+      .iter()
+      .filter(|(_, object)| object.vertex_shader == original || object.fragment_shader == original)
+      .map(|(id, _)| *id)
+      .collect();
+
+    let mut relinked = Vec::with_capacity(affected.len());
+    for id in &affected {
+      match self.relink(*id) {
+        Ok(resources) => relinked.push(resources),
+        Err(e) => {
+          match previous {
+            Some(source) => self.shader_overrides.insert(original.to_owned(), source),
+            None => self.shader_overrides.remove(original),
+          };
+          for (program, vertex_array) in relinked {
+            self.backend.delete_vertex_array(vertex_array);
+            self.backend.release_program(program);
+          }
+          return Err(e);
+        }
+      }
+    }
+
+    for (id, (program, vertex_array)) in affected.iter().zip(relinked) {
+      let object = self.objects.get_mut(id).expect("object to relink");
+      let vertex_array = std::mem::replace(&mut object.vertex_array, vertex_array);
+      let program = std::mem::replace(&mut object.program, program);
+      // linked again with the replaced vertex shader at next pick
+      let pick = object.pick.take();
+      for (program, vertex_array) in Some((program, vertex_array)).into_iter().chain(pick) {
+        self.backend.delete_vertex_array(vertex_array);
+        // old sources are not linked again unless the replacement is undone
+        self.backend.retire_program(program);
+      }
+    }
+    Ok(affected.len())
+  }
+
+  /// link the object with current sources, into a new vertex array pointing to its buffers
+  fn relink(&mut self, id: ObjectId) -> Result<(B::Program, B::VertexArray), TriadicaError> {
+    let object = &self.objects[&id];
+    let (key, vertex_shader, fragment_shader) = sources_to_link(
+      &self.shader_overrides,
+      self.shader_upgrade,
+      object.program_key,
+      &object.vertex_shader,
+      &object.fragment_shader,
+    );
+    let program = self.backend.create_program(key, &vertex_shader, &fragment_shader)?;
+    let vertex_array = match self.backend.create_vertex_array() {
+      Ok(vertex_array) => vertex_array,
+      Err(e) => {
+        self.backend.release_program(program);
+        return Err(e);
+      }
+    };
+    self.backend.bind_vertex_array(Some(&vertex_array));
+    let pointed = point_attributes(&mut self.backend, &program, &object.attributes, &object.buffers);
+    if let Some(index_buffer) = &object.index_buffer {
+      self.backend.bind_index_buffer(index_buffer);
+    }
+    self.backend.bind_vertex_array(None);
+    match pointed {
+      Ok(()) => {
+        self
+          .warnings
+          .check_attributes(id, self.backend.reflection(&program), &object.attributes);
+        Ok((program, vertex_array))
+      }
+      Err(e) => {
+        self.backend.delete_vertex_array(vertex_array);
+        self.backend.release_program(program);
+        Err(e)
+      }
+    }
+  }
+
+  /// warnings about data not matching programs since last call, like attributes without data
+  /// or uniforms set but not read. each warning is returned once
+  pub fn take_warnings(&mut self) -> Vec<ProgramWarning> {
+    std::mem::take(&mut self.warnings.pending)
+  }
+
+  /// delete GPU resources of an object, call this when the object leaves the tree
+  pub fn dispose_object(&mut self, id: ObjectId) {
+    if let Some(object) = self.objects.remove(&id) {
+      for (program, vertex_array) in Some((object.program, object.vertex_array)).into_iter().chain(object.pick) {
+        self.backend.release_program(program);
+        self.backend.delete_vertex_array(vertex_array);
+      }
+      for buffer in object.buffers.into_iter().chain(object.index_buffer) {
+        self.backend.delete_buffer(buffer);
+      }
+    }
+  }
+
+  /// delete a texture on GPU, it is uploaded again when an object using it is drawn
+  pub fn dispose_texture(&mut self, id: TextureId) {
+    self.pending_textures.remove(&id);
+    if let Some(texture) = self.textures.remove(&id) {
+      self.backend.delete_texture(texture);
+    }
+  }
+
+  /// delete GPU resources of every object in the tree, along with textures no other uploaded object uses
+  pub fn dispose_tree(&mut self, tree: &TriadicaElementTree) {
+    let mut textures = HashSet::new();
+    for item in tree.objects() {
+      self.dispose_object(item.id);
+      textures.extend(item.textures.iter().map(|(_, texture)| texture.id));
+    }
+    self.dispose_unused_textures(textures);
+  }
+
+  /// delete textures among `candidates` that no uploaded object uses
+  fn dispose_unused_textures(&mut self, candidates: HashSet<TextureId>) {
+    let used: HashSet<TextureId> = self.objects.values().flat_map(|object| object.textures.iter().copied()).collect();
+    for id in candidates.difference(&used) {
+      self.dispose_texture(*id);
+    }
+  }
+
+  /// delete GPU resources of objects and textures not in the tree, call this after replacing the tree
+  pub fn retain_tree(&mut self, tree: &TriadicaElementTree) {
+    let alive: HashSet<ObjectId> = tree.objects().iter().map(|item| item.id).collect();
+    let stale: Vec<ObjectId> = self.objects.keys().filter(|id| !alive.contains(id)).copied().collect();
+    for id in stale {
+      self.dispose_object(id);
+    }
+    self.dispose_unused_textures(self.textures.keys().copied().collect());
+  }
+
+  /// forget GPU resources after the context is lost, they are invalid and can not be deleted.
+  /// painting is skipped until `context_restored`
+  pub fn context_lost(&mut self) {
+    self.context_lost = true;
+    self.backend.forget_resources();
+    self.objects.clear();
+    self.textures.clear();
+    self.pending_textures.clear();
+    for slot in self.targets.values_mut() {
+      slot.allocated = None;
+    }
+    self.render_state = None;
+  }
+
+  /// upload objects of the tree again into the restored context, with replaced shaders kept.
+  /// render targets and objects of screen passes are created again when drawn
+  pub fn context_restored(&mut self, tree: &TriadicaElementTree) -> Result<(), TriadicaError> {
+    self.context_lost = false;
+    self.upload_tree(tree)
+  }
+
+  pub fn is_context_lost(&self) -> bool {
+    self.context_lost
+  }
+
+  /// draw the tree to the canvas with current viewer states, objects are uploaded on first paint
+  pub fn paint(&mut self, tree: &TriadicaElementTree) -> Result<(), TriadicaError> {
+    self.paint_into(None, tree)
+  }
+
+  /// declare an offscreen target, replacing the one with same name
+  pub fn set_render_target(&mut self, spec: RenderTargetSpec) {
+    self.dispose_render_target(&spec.name);
+    self.targets.insert(spec.name.to_owned(), TargetSlot { spec, allocated: None });
+  }
+
+  /// delete a render target and its GPU resources
+  pub fn dispose_render_target(&mut self, name: &str) {
+    if let Some(TargetSlot {
+      allocated: Some((target, _)),
+      ..
+    }) = self.targets.remove(name)
+    {
+      self.backend.delete_render_target(target);
+    }
+  }
+
+  /// bind a target for drawing, allocated at first use and again after the canvas resized.
+  /// returns size in pixels
+  fn bind_target(&mut self, name: Option<&str>) -> Result<(i32, i32), TriadicaError> {
+    let canvas = self.backend.drawing_buffer_size();
+    let name = match name {
+      Some(name) => name,
+      None => {
+        self.backend.bind_render_target(None);
+        return Ok(canvas);
+      }
+    };
+    let slot = self
+      .targets
+      .get_mut(name)
+      .ok_or_else(|| TriadicaError::Other(format!("unknown render target `{name}`")))?;
+    let size = slot.spec.size_for(canvas);
+    if slot.allocated.as_ref().map(|(_, s)| *s) != Some(size) {
+      if let Some((target, _)) = slot.allocated.take() {
+        self.backend.delete_render_target(target);
+      }
+      let target = self.backend.create_render_target(size.0, size.1, slot.spec.depth)?;
+      slot.allocated = Some((target, size));
+    }
+    let (target, _) = slot.allocated.as_ref().expect("allocated target");
+    self.backend.bind_render_target(Some(target));
+    Ok(size)
+  }
+
+  /// send render state to the backend when it differs from the last one
+  fn apply_render_state(&mut self, state: &RenderState) {
+    if self.render_state.as_ref() != Some(state) {
+      self.backend.set_render_state(state);
+      self.render_state = Some(*state);
+    }
+  }
+
+  /// clear a render target, `None` for the canvas, and draw the tree into it.
+  /// opaque objects are drawn in tree order, then transparent ones from back to front
+  pub fn paint_into(&mut self, target: Option<&str>, tree: &TriadicaElementTree) -> Result<(), TriadicaError> {
+    if self.context_lost {
+      return Ok(());
+    }
+    self.bind_target(target)?;
+    // depth is only cleared with depth writes on
+    self.apply_render_state(&RenderState::default());
+    self.backend.clear();
+    self.draw_tree(tree, &viewer::get_camera())
+  }
+
+  /// draw the tree into rectangles of a render target, `None` for the canvas, each with its own camera.
+  /// only the rectangles are cleared, so later viewports can be insets over earlier ones
+  pub fn paint_viewports(
+    &mut self,
+    target: Option<&str>,
+    tree: &TriadicaElementTree,
+    viewports: &[Viewport],
+  ) -> Result<(), TriadicaError> {
+    if self.context_lost {
+      return Ok(());
+    }
+    let size = self.bind_target(target)?;
+    for viewport in viewports {
+      let (x, y, width, height) = viewport.rect.pixels(size);
+      if width <= 0 || height <= 0 {
+        continue;
+      }
+      self.backend.set_viewport(x, y, width, height);
+      self.apply_render_state(&RenderState::default());
+      self.backend.clear();
+      let camera = Camera {
+        viewport_ratio: height as f32 / width as f32,
+        ..viewport.camera
+      };
+      self.draw_tree(tree, &camera)?;
+    }
+    self.backend.set_viewport(0, 0, size.0, size.1);
+    Ok(())
+  }
+
+  /// draw the tree once per eye, eyes are apart along `rightward` of the viewer camera
+  pub fn paint_stereo(
+    &mut self,
+    target: Option<&str>,
+    tree: &TriadicaElementTree,
+    options: &StereoOptions,
+  ) -> Result<(), TriadicaError> {
+    if self.context_lost {
+      return Ok(());
+    }
+    let [left, right] = viewer::get_camera().stereo_eyes(options.eye_separation);
+    match options.output {
+      StereoOutput::SideBySide => {
+        let [left_rect, right_rect] = ViewportRect::split_horizontal();
+        self.paint_viewports(target, tree, &[Viewport::new(left_rect, left), Viewport::new(right_rect, right)])
+      }
+      StereoOutput::Anaglyph => {
+        self.bind_target(target)?;
+        self.apply_render_state(&RenderState::default());
+        self.backend.clear();
+        self.backend.set_color_mask(true, false, false, true);
+        let drawn = self.draw_tree(tree, &left).and_then(|_| {
+          // only clears green, blue and depth, red and alpha of the left eye are kept
+          self.apply_render_state(&RenderState::default());
+          self.backend.set_color_mask(false, true, true, false);
+          self.backend.clear();
+          self.draw_tree(tree, &right)
+        });
+        self.backend.set_color_mask(true, true, true, true);
+        drawn
+      }
+    }
+  }
+
+  /// draw objects of the tree into the bound target with a camera, without clearing.
+  /// objects out of view are uploaded but not drawn
+  fn draw_tree(&mut self, tree: &TriadicaElementTree, camera: &Camera) -> Result<(), TriadicaError> {
+    let mut objects = tree.objects();
+    objects.retain(|item| !item.is_empty());
+    for item in &objects {
+      self.upload_object(item)?;
+    }
+    if self.culling {
+      let count = objects.len();
+      objects.retain(|item| item.bounds.is_none_or(|bounds| bounds.is_visible(camera)));
+      self.stats.culled += (count - objects.len()) as u64;
+    }
+    self.stats.draws += objects.len() as u64;
+    let (opaque, mut transparent): (Vec<_>, Vec<_>) = objects.into_iter().partition(|item| !item.render_state.is_transparent());
+    let depth = |item: &ComponentCache| (self.objects[&item.id].center - camera.position).dot(camera.forward);
+    transparent.sort_by(|a, b| depth(b).total_cmp(&depth(a)));
+
+    for item in opaque.into_iter().chain(transparent) {
+      self.draw_object(item, DrawContext::Scene(camera))?;
+    }
+    self.backend.bind_vertex_array(None);
+    self.apply_render_state(&RenderState::default());
+
+    Ok(())
+  }
+
+  /// run a full-screen pass into a render target, `None` for the canvas.
+  /// a pass should not read the target it writes to
+  pub fn run_screen_pass(&mut self, pass: &ScreenPass, target: Option<&str>) -> Result<(), TriadicaError> {
+    if self.context_lost {
+      return Ok(());
+    }
+    if let Some(name) = target {
+      if pass.inputs.iter().any(|(_, input)| input == name) {
+        return Err(TriadicaError::Other(format!("screen pass reads and writes render target `{name}`")));
+      }
+    }
+    let resolution = self.bind_target(target)?;
+    self.backend.clear();
+    self.draw_object(
+      &pass.object,
+      DrawContext::Screen {
+        inputs: &pass.inputs,
+        resolution,
+      },
+    )?;
+    self.backend.bind_vertex_array(None);
+    self.apply_render_state(&RenderState::default());
+    Ok(())
+  }
+
+  /// upload the object when needed and draw it
+  fn draw_object(&mut self, item: &ComponentCache, context: DrawContext) -> Result<(), TriadicaError> {
+    if item.is_empty() {
+      return Ok(());
+    }
+    self.upload_object(item)?;
+    self.upload_textures(item)?;
+    self.apply_render_state(&item.render_state);
+    let object = &self.objects[&item.id];
+    let backend = &mut self.backend;
+    backend.use_program(&object.program);
+    let inputs = match context {
+      DrawContext::Scene(camera) => {
+        bind_uniforms(backend, &object.program, camera)?;
+        &[][..]
+      }
+      DrawContext::Screen { inputs, resolution } => {
+        let size = Vec2::new(resolution.0 as f32, resolution.1 as f32);
+        backend.set_uniform(&object.program, "u_resolution", &size.into())?;
+        inputs
+      }
+    };
+    if item.draw_mode == DrawMode::Points {
+      backend.set_uniform(&object.program, POINT_SIZE_UNIFORM, &UniformValue::Float(item.point_size))?;
+    }
+    let uniforms = (item.get_uniforms)();
+    let reflection = backend.reflection(&object.program);
+    let unused = uniforms
+      .keys()
+      .chain(item.textures.iter().map(|(sampler, _)| sampler))
+      .filter(|name| !reflection.has_uniform(name))
+      .cloned()
+      .collect::<Vec<_>>();
+    for name in unused {
+      self.warnings.report(ProgramWarning::UnusedUniform { object: item.id, name });
+    }
+    for (name, value) in uniforms {
+      backend
+        .set_uniform(&object.program, &name, &value)
+        .map_err(|e| TriadicaError::Other(format!("failed to bind uniform `{name}`: {e}")))?;
+    }
+    for (unit, (sampler, texture)) in item.textures.iter().enumerate() {
+      backend.bind_texture(unit as u32, self.textures.get(&texture.id));
+      backend.set_uniform(&object.program, sampler, &UniformValue::Sampler(unit as u32))?;
+    }
+    for (idx, (sampler, name)) in inputs.iter().enumerate() {
+      let unit = (item.textures.len() + idx) as u32;
+      let target = match self.targets.get(name) {
+        Some(TargetSlot {
+          allocated: Some((target, _)),
+          ..
+        }) => target,
+        _ => return Err(TriadicaError::Other(format!("render target `{name}` is not drawn yet"))),
+      };
+      backend.bind_texture(unit, Some(backend.target_texture(target)));
+      backend.set_uniform(&object.program, sampler, &UniformValue::Sampler(unit))?;
+    }
+    backend.bind_vertex_array(Some(&object.vertex_array));
+    draw_call(backend, item);
+    Ok(())
+  }
+
+  /// object under a pixel, in pixels of the canvas from the top left, by drawing IDs of objects with `camera`
+  /// into an offscreen target and reading the pixel back. unlike `pick_tree`, positions computed in vertex shaders
+  /// are respected. the part of the object is then found on CPU, falling back to its vertex nearest to the pixel.
+  /// objects without `a_position` and empty objects are not drawn
+  pub fn pick_by_id(&mut self, tree: &TriadicaElementTree, camera: &Camera, pixel: Vec2) -> Result<Option<PickHit>, TriadicaError> {
+    if self.context_lost {
+      return Ok(None);
+    }
+    if !self.targets.contains_key(PICK_TARGET) {
+      self.set_render_target(RenderTargetSpec::new(PICK_TARGET, 1.0, true));
+    }
+    let size = self.bind_target(Some(PICK_TARGET))?;
+    let (x, y) = (pixel.x.floor() as i32, size.1 - 1 - pixel.y.floor() as i32);
+    if x < 0 || y < 0 || x >= size.0 || y >= size.1 {
+      self.backend.bind_render_target(None);
+      return Ok(None);
+    }
+
+    let objects: Vec<_> = tree
+      .objects_with_paths()
+      .into_iter()
+      .filter(|(_, item)| !item.is_empty() && item.attr_names.iter().any(|(name, _)| name == POSITION_ATTRIBUTE))
+      .collect();
+    self.apply_render_state(&RenderState::default());
+    self.backend.clear();
+    for (idx, (_, item)) in objects.iter().enumerate() {
+      self.draw_pick_id(item, camera, idx as u32 + 1)?;
+    }
+    self.backend.bind_vertex_array(None);
+    self.apply_render_state(&RenderState::default());
+    let id = decode_pick_id(self.backend.read_pixel(x, y)?) as usize;
+    self.backend.bind_render_target(None);
+
+    let (path, item) = match id.checked_sub(1).and_then(|idx| objects.get(idx)) {
+      Some(found) => found,
+      None => return Ok(None),
+    };
+    match pick_object(path, item, camera, size, pixel, DEFAULT_PICK_TOLERANCE)? {
+      Some(hit) => Ok(Some(hit)),
+      None => nearest_vertex(path, item, camera, size, pixel),
+    }
+  }
+
+  /// draw an object with its pick program, writing the color of `id`
+  fn draw_pick_id(&mut self, item: &ComponentCache, camera: &Camera, id: u32) -> Result<(), TriadicaError> {
+    self.upload_object(item)?;
+    self.upload_textures(item)?;
+    self.link_pick_program(item.id)?;
+    // IDs are not blended, and every object writes depth so the nearest one is kept
+    self.apply_render_state(&RenderState {
+      blend: BlendMode::Opaque,
+      depth_test: true,
+      depth_write: true,
+      ..item.render_state
+    });
+    let object = &self.objects[&item.id];
+    let (program, vertex_array) = object.pick.as_ref().expect("pick program linked");
+    let backend = &mut self.backend;
+    backend.use_program(program);
+    bind_uniforms(backend, program, camera)?;
+    if item.draw_mode == DrawMode::Points {
+      backend.set_uniform(program, POINT_SIZE_UNIFORM, &UniformValue::Float(item.point_size))?;
+    }
+    // vertex shaders may read them, inactive ones are skipped
+    for (name, value) in (item.get_uniforms)() {
+      backend.set_uniform(program, &name, &value)?;
+    }
+    for (unit, (sampler, texture)) in item.textures.iter().enumerate() {
+      backend.bind_texture(unit as u32, self.textures.get(&texture.id));
+      backend.set_uniform(program, sampler, &UniformValue::Sampler(unit as u32))?;
+    }
+    backend.set_uniform(program, PICK_ID_UNIFORM, &UniformValue::Vec4(encode_pick_id(id)))?;
+    backend.bind_vertex_array(Some(vertex_array));
+    draw_call(backend, item);
+    Ok(())
+  }
+
+  /// link vertex shader of an uploaded object with `PICK_FRAGMENT_SHADER`, into a vertex array pointing to its buffers.
+  /// skipped when already linked
+  fn link_pick_program(&mut self, id: ObjectId) -> Result<(), TriadicaError> {
+    let object = &self.objects[&id];
+    if object.pick.is_some() {
+      return Ok(());
+    }
+    // the fragment shader is 1.00, so it is upgraded along with 3.00 vertex shaders
+    let upgrade = match self.shader_upgrade {
+      ShaderUpgrade::Never => ShaderUpgrade::Mixed,
+      upgrade => upgrade,
+    };
+    let key = ProgramKey::of(&object.vertex_shader, PICK_FRAGMENT_SHADER);
+    let (key, vertex_shader, fragment_shader) =
+      sources_to_link(&self.shader_overrides, upgrade, key, &object.vertex_shader, PICK_FRAGMENT_SHADER);
+    let program = self.backend.create_program(key, &vertex_shader, &fragment_shader)?;
+    let vertex_array = match self.backend.create_vertex_array() {
+      Ok(vertex_array) => vertex_array,
+      Err(e) => {
+        self.backend.release_program(program);
+        return Err(e);
+      }
+    };
+    self.backend.bind_vertex_array(Some(&vertex_array));
+    // attributes only passed to varyings are optimized out, since the fragment shader reads none
+    let pointed = point_attributes(&mut self.backend, &program, &object.attributes, &object.buffers);
+    if let Some(index_buffer) = &object.index_buffer {
+      self.backend.bind_index_buffer(index_buffer);
+    }
+    self.backend.bind_vertex_array(None);
+    if let Err(e) = pointed {
+      self.backend.delete_vertex_array(vertex_array);
+      self.backend.release_program(program);
+      return Err(e);
+    }
+    self.objects.get_mut(&id).expect("uploaded object").pick = Some((program, vertex_array));
+    Ok(())
+  }
+}
+
+/// draw the bound vertex array as the object says, with indices and instances when it has them
+fn draw_call<B: RenderBackend>(backend: &mut B, item: &ComponentCache) {
+  match (&item.indices, item.instances.as_ref().map(|x| x.count)) {
+    (Some(indices), None) => backend.draw_elements(item.draw_mode, indices.len() as i32, indices.index_type()),
+    (None, None) => backend.draw(item.draw_mode, 0, item.size as i32),
+    (Some(indices), Some(n)) => backend.draw_elements_instanced(item.draw_mode, indices.len() as i32, indices.index_type(), n as i32),
+    (None, Some(n)) => backend.draw_instanced(item.draw_mode, 0, item.size as i32, n as i32),
+  }
+}
+
+/// average of positions, moved by average `a_offset` of instances. origin when not found
+fn center_of(item: &ComponentCache) -> Vec3 {
+  let mean = |attr_names: &[(String, i8)], arrays: &[Vec<f32>], name: &str| {
+    let idx = attr_names.iter().position(|(n, size)| n == name && *size == 3)?;
+    let data = arrays.get(idx)?;
+    let count = data.len() / 3;
+    if count == 0 {
+      return None;
+    }
+    let sum = data.chunks_exact(3).fold(Vec3::ZERO, |acc, p| acc + Vec3::new(p[0], p[1], p[2]));
+    Some(sum / count as f32)
+  };
+  let center = mean(&item.attr_names, &item.arrays, POSITION_ATTRIBUTE).unwrap_or(Vec3::ZERO);
+  let offset = item
+    .instances
+    .as_ref()
+    .and_then(|instances| mean(&instances.attr_names, &instances.arrays, "a_offset"))
+    .unwrap_or(Vec3::ZERO);
+  center + offset
+}
+
+/// uniforms and inputs that differ between drawing a tree and a screen pass
+enum DrawContext<'a> {
+  Scene(&'a Camera),
+  /// render targets are bound to samplers after the object's own textures
+  Screen {
+    inputs: &'a [(String, String)],
+    resolution: (i32, i32),
+  },
+}
+
+/// sources after replacing and upgrading, with their key. `key` of the original sources is reused when nothing changes
+fn sources_to_link<'a>(
+  overrides: &'a HashMap<String, String>,
+  upgrade: ShaderUpgrade,
+  key: ProgramKey,
+  vertex_shader: &'a str,
+  fragment_shader: &'a str,
+) -> (ProgramKey, Cow<'a, str>, Cow<'a, str>) {
+  let replaced_vertex = overrides.get(vertex_shader);
+  let replaced_fragment = overrides.get(fragment_shader);
+  let (vertex_shader, fragment_shader) = upgrade.apply(
+    replaced_vertex.map_or(vertex_shader, String::as_str),
+    replaced_fragment.map_or(fragment_shader, String::as_str),
+  );
+  let unchanged = replaced_vertex.is_none()
+    && replaced_fragment.is_none()
+    && matches!((&vertex_shader, &fragment_shader), (Cow::Borrowed(_), Cow::Borrowed(_)));
+  let key = if unchanged {
+    key
+  } else {
+    ProgramKey::of(&vertex_shader, &fragment_shader)
+  };
+  (key, vertex_shader, fragment_shader)
+}
+
+/// attributes of the object, per-vertex ones first, then per-instance ones with divisor 1
+fn attributes_of(item: &ComponentCache) -> Vec<Attribute> {
+  let per_vertex = item.attr_names.iter().map(|(name, size)| (name.to_owned(), *size as i32, 0));
+  let per_instance = item
+    .instances
+    .iter()
+    .flat_map(|instances| instances.attr_names.iter())
+    .map(|(name, size)| (name.to_owned(), *size as i32, 1));
+  per_vertex.chain(per_instance).collect()
+}
+
+/// upload data of every attribute, in order of `attributes_of`
+fn create_buffers<B: RenderBackend>(backend: &mut B, item: &ComponentCache, buffers: &mut Vec<B::Buffer>) -> Result<(), TriadicaError> {
+  let per_instance = item.instances.iter().flat_map(|instances| instances.arrays.iter());
+  for data in item.arrays.iter().chain(per_instance) {
+    buffers.push(backend.create_buffer(data)?);
+  }
+  Ok(())
+}
+
+/// point attributes of the bound vertex array to buffers, each at its own location in the program
+fn point_attributes<B: RenderBackend>(
+  backend: &mut B,
+  program: &B::Program,
+  attributes: &[Attribute],
+  buffers: &[B::Buffer],
+) -> Result<(), TriadicaError> {
+  for ((name, size, divisor), buffer) in attributes.iter().zip(buffers) {
+    // inactive attributes have no location, they are reported by `Warnings::check_attributes` instead
+    if backend.reflection(program).has_attribute(name) {
+      backend.attrib_pointer(program, name, buffer, *size, *divisor)?;
+    }
+  }
+  Ok(())
+}
+
+/// bind uniforms of camera, shared by all programs
+fn bind_uniforms<B: RenderBackend>(backend: &mut B, program: &B::Program, camera: &Camera) -> Result<(), TriadicaError> {
+  // directions
+  backend.set_uniform(program, "forward", &camera.forward.into())?;
+  backend.set_uniform(program, "upward", &camera.upward.into())?;
+  backend.set_uniform(program, "rightward", &camera.rightward.into())?;
+
+  // lookDistance, defaults to 600
+  backend.set_uniform(program, "lookDistance", &UniformValue::Float(camera.look_distance))?;
+
+  // backcone scale
+  backend.set_uniform(program, "coneBackScale", &UniformValue::Float(camera.cone_back_scale))?;
+
+  // viewportRatio
+  backend.set_uniform(program, "viewportRatio", &UniformValue::Float(camera.viewport_ratio))?;
+
+  // cameraPosition
+  backend.set_uniform(program, "cameraPosition", &camera.position.into())?;
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::rc::Rc;
+
+  use super::*;
+  use crate::alias::{group, object};
+  use crate::backend::{BackendCall, RecordingBackend};
+  use crate::component::{PackedAttrs, TriadicaElement};
+  use crate::primes::VertexDataValue;
+  use crate::state::{CullFace, PolygonOffset};
+  use crate::stereo::DEFAULT_EYE_SEPARATION;
+  use crate::texture::{Texture, TextureOptions, TextureWrap};
+  use crate::uniform::UniformMap;
+
+  const VERTEX: &str = "#version 300 es
+in vec3 a_position;
+void main() {
+  gl_Position = vec4(a_position, 1.0);
+}
+";
+
+  const FRAGMENT: &str = "#version 300 es
+precision mediump float;
+out vec4 color;
+void main() {
+  color = vec4(1.0);
+}
+";
+
+  fn triangle() -> TriadicaElement {
+    triangle_with(Rc::new(UniformMap::default))
+  }
+
+  fn triangle_with(get_uniforms: Rc<dyn Fn() -> UniformMap>) -> TriadicaElement {
+    object(
+      DrawMode::Triangles,
+      VERTEX.to_owned(),
+      FRAGMENT.to_owned(),
+      vec![("a_position".to_owned(), 3)],
+      PackedAttrs::List(
+        [[0.0, 0.0, 0.0], [10.0, 0.0, 0.0], [0.0, 10.0, 0.0]]
+          .into_iter()
+          .map(|p| PackedAttrs::Item(vec![VertexDataValue::Vec3(p)]))
+          .collect(),
+      ),
+      get_uniforms,
+    )
+  }
+
+  fn textured(texture: &Texture) -> TriadicaElement {
+    triangle().with_texture("u_texture", texture.clone())
+  }
+
+  fn renderer() -> Renderer<RecordingBackend> {
+    Renderer::new(RecordingBackend::default())
+  }
+
+  /// names of calls without their fields, uniforms are left out
+  fn kinds(calls: &[BackendCall]) -> Vec<String> {
+    calls
+      .iter()
+      .filter(|call| !matches!(call, BackendCall::SetUniform { .. }))
+      .map(|call| {
+        let text = format!("{call:?}");
+        text.split([' ', '(']).next().unwrap_or_default().to_owned()
+      })
+      .collect()
+  }
+
+  #[test]
+  fn uploads_once_and_draws_every_frame() {
+    let tree = triangle().compile_to_tree().unwrap();
+    let mut renderer = renderer();
+    renderer.paint(&tree).unwrap();
+    let calls = renderer.backend.take_calls();
+    assert_eq!(
+      kinds(&calls),
+      [
+        "BindRenderTarget",
+        "SetRenderState",
+        "Clear",
+        "CreateProgram",
+        "CreateVertexArray",
+        "BindVertexArray",
+        "CreateBuffer",
+        "AttribPointer",
+        "BindVertexArray",
+        "UseProgram",
+        "BindVertexArray",
+        "Draw",
+        "BindVertexArray",
+      ]
+    );
+    assert!(calls.contains(&BackendCall::AttribPointer {
+      program: 0,
+      attr_name: "a_position".to_owned(),
+      buffer: 0,
+      unit_size: 3,
+      divisor: 0,
+    }));
+    assert!(calls.contains(&BackendCall::SetUniform {
+      program: 0,
+      name: "lookDistance".to_owned(),
+      value: UniformValue::Float(600.0),
+    }));
+
+    renderer.paint(&tree).unwrap();
+    assert_eq!(
+      kinds(&renderer.backend.take_calls()),
+      [
+        "BindRenderTarget",
+        "Clear",
+        "UseProgram",
+        "BindVertexArray",
+        "Draw",
+        "BindVertexArray"
+      ]
+    );
+    assert_eq!(renderer.uploaded_count(), 1);
+  }
+
+  #[test]
+  fn uploads_again_after_context_is_restored() {
+    let tree = triangle().compile_to_tree().unwrap();
+    let mut renderer = renderer();
+    renderer.paint(&tree).unwrap();
+    renderer.backend.take_calls();
+
+    renderer.context_lost();
+    assert!(renderer.is_context_lost());
+    assert_eq!(renderer.uploaded_count(), 0);
+    assert_eq!(
+      (
+        renderer.backend.live_programs(),
+        renderer.backend.live_buffers(),
+        renderer.backend.live_vertex_arrays()
+      ),
+      (0, 0, 0)
+    );
+    renderer.paint(&tree).unwrap();
+    assert_eq!(renderer.backend.take_calls(), [], "nothing is painted without a context");
+
+    renderer.context_restored(&tree).unwrap();
+    assert!(!renderer.is_context_lost());
+    assert_eq!(
+      kinds(&renderer.backend.take_calls()),
+      [
+        "CreateProgram",
+        "CreateVertexArray",
+        "BindVertexArray",
+        "CreateBuffer",
+        "AttribPointer",
+        "BindVertexArray"
+      ]
+    );
+    assert_eq!(
+      (
+        renderer.backend.live_programs(),
+        renderer.backend.live_buffers(),
+        renderer.backend.live_vertex_arrays()
+      ),
+      (1, 1, 1)
+    );
+
+    renderer.paint(&tree).unwrap();
+    let calls = renderer.backend.take_calls();
+    assert_eq!(
+      kinds(&calls),
+      [
+        "BindRenderTarget",
+        "SetRenderState",
+        "Clear",
+        "UseProgram",
+        "BindVertexArray",
+        "Draw",
+        "BindVertexArray"
+      ],
+      "render states are sent again"
+    );
+    assert!(calls.contains(&BackendCall::UseProgram(1)), "program is linked again");
+    assert_eq!(renderer.uploaded_count(), 1);
+  }
+
+  #[test]
+  fn paints_viewports_with_ratios_of_their_rects() {
+    let tree = triangle().compile_to_tree().unwrap();
+    let mut renderer = renderer();
+    let camera = Camera::looking_at(Vec3::new(0.0, 0.0, 600.0), Vec3::ZERO, Vec3::Y);
+    let [left, right] = ViewportRect::split_horizontal();
+    let viewports = [
+      Viewport::new(left, camera),
+      Viewport::new(right, camera),
+      Viewport::new(ViewportRect::new(0.5, 0.5, 0.0, 0.5), camera),
+      Viewport::new(ViewportRect::new(0.75, 0.75, 0.25, 0.25), camera),
+    ];
+    renderer.paint_viewports(None, &tree, &viewports).unwrap();
+
+    let calls = renderer.backend.take_calls();
+    let rects: Vec<_> = calls
+      .iter()
+      .filter_map(|call| match call {
+        BackendCall::SetViewport { x, y, width, height } => Some((*x, *y, *width, *height)),
+        _ => None,
+      })
+      .collect();
+    assert_eq!(
+      rects,
+      [(0, 0, 400, 600), (400, 0, 400, 600), (600, 450, 200, 150), (0, 0, 800, 600)],
+      "zero-size rects are skipped, then the whole target is restored"
+    );
+    let ratios: Vec<_> = calls
+      .iter()
+      .filter_map(|call| match call {
+        BackendCall::SetUniform { name, value, .. } if name == "viewportRatio" => Some(value.clone()),
+        _ => None,
+      })
+      .collect();
+    assert_eq!(ratios, [1.5, 1.5, 0.75].map(UniformValue::Float));
+    assert_eq!(calls.iter().filter(|call| matches!(call, BackendCall::Draw { .. })).count(), 3);
+  }
+
+  #[test]
+  fn paints_anaglyph_eyes_into_color_channels() {
+    let tree = triangle().compile_to_tree().unwrap();
+    let mut renderer = renderer();
+    renderer.paint_stereo(None, &tree, &StereoOptions::anaglyph()).unwrap();
+
+    let calls = renderer.backend.take_calls();
+    let sequence: Vec<_> = calls
+      .iter()
+      .filter(|call| matches!(call, BackendCall::Clear | BackendCall::SetColorMask(_) | BackendCall::Draw { .. }))
+      .cloned()
+      .collect();
+    let draw = BackendCall::Draw {
+      mode: DrawMode::Triangles,
+      first: 0,
+      count: 3,
+    };
+    assert_eq!(
+      sequence,
+      [
+        BackendCall::Clear,
+        BackendCall::SetColorMask([true, false, false, true]),
+        draw.clone(),
+        BackendCall::SetColorMask([false, true, true, false]),
+        BackendCall::Clear,
+        draw,
+        BackendCall::SetColorMask([true, true, true, true]),
+      ]
+    );
+
+    let positions: Vec<_> = calls
+      .iter()
+      .filter_map(|call| match call {
+        BackendCall::SetUniform { name, value, .. } if name == "cameraPosition" => Some(value.clone()),
+        _ => None,
+      })
+      .collect();
+    let [left, right] = viewer::get_camera().stereo_eyes(DEFAULT_EYE_SEPARATION);
+    assert_eq!(positions, [left.position, right.position].map(UniformValue::Vec3));
+  }
+
+  #[test]
+  fn binds_camera_uniforms_then_object_uniforms() {
+    let camera = Camera::looking_at(Vec3::new(0.0, 0.0, 600.0), Vec3::ZERO, Vec3::Y);
+    let uniforms = || {
+      let mut uniforms = UniformMap::new();
+      uniforms.insert("u_weights".to_owned(), vec![0.5_f32, 1.0].into());
+      uniforms.insert("u_matrix".to_owned(), glam::Mat2::IDENTITY.into());
+      uniforms
+    };
+    let tree = triangle_with(Rc::new(uniforms)).compile_to_tree().unwrap();
+    let mut renderer = renderer();
+    renderer
+      .paint_viewports(None, &tree, &[Viewport::new(ViewportRect::FULL, camera)])
+      .unwrap();
+    let set: Vec<(String, UniformValue)> = renderer
+      .backend
+      .take_calls()
+      .into_iter()
+      .filter_map(|call| match call {
+        BackendCall::SetUniform { name, value, .. } => Some((name, value)),
+        _ => None,
+      })
+      .collect();
+    let expected = [
+      ("forward", camera.forward.into()),
+      ("upward", camera.upward.into()),
+      ("rightward", camera.rightward.into()),
+      ("lookDistance", UniformValue::Float(600.0)),
+      ("coneBackScale", UniformValue::Float(camera.cone_back_scale)),
+      // of the 800x600 canvas `RecordingBackend` reports
+      ("viewportRatio", UniformValue::Float(0.75)),
+      ("cameraPosition", camera.position.into()),
+      // sorted by name
+      ("u_matrix", UniformValue::Mat2(glam::Mat2::IDENTITY)),
+      (
+        "u_weights",
+        UniformValue::Array(vec![UniformValue::Float(0.5), UniformValue::Float(1.0)]),
+      ),
+    ];
+    assert_eq!(set, expected.map(|(name, value)| (name.to_owned(), value)));
+  }
+
+  #[test]
+  fn fails_on_uniform_arrays_mixing_kinds() {
+    let tree = triangle_with(Rc::new(|| {
+      let mut uniforms = UniformMap::new();
+      uniforms.insert("u_bad".to_owned(), UniformValue::Array(vec![1.0.into(), 1.into()]));
+      uniforms
+    }))
+    .compile_to_tree()
+    .unwrap();
+    let error = renderer().paint(&tree).unwrap_err();
+    assert!(error.to_string().contains("failed to bind uniform `u_bad`"), "{error}");
+  }
+
+  #[test]
+  fn reads_render_targets_in_screen_passes() {
+    let tree = triangle().compile_to_tree().unwrap();
+    let mut renderer = renderer();
+    renderer.set_render_target(RenderTargetSpec::new("scene", 0.5, true));
+    renderer.paint_into(Some("scene"), &tree).unwrap();
+    let calls = renderer.backend.take_calls();
+    assert_eq!(
+      calls[..2],
+      [
+        BackendCall::CreateRenderTarget {
+          target: 0,
+          texture: 0,
+          width: 400,
+          height: 300,
+          depth: true,
+        },
+        BackendCall::BindRenderTarget(Some(0)),
+      ]
+    );
+
+    let blur = "precision mediump float;\nvarying vec2 v_uv;\nuniform sampler2D u_scene;\nvoid main() {\n  gl_FragColor = texture2D(u_scene, v_uv);\n}\n";
+    let pass = ScreenPass::new(blur, vec![("u_scene".to_owned(), "scene".to_owned())], Rc::new(UniformMap::default));
+    renderer.run_screen_pass(&pass, None).unwrap();
+    let calls = renderer.backend.take_calls();
+    assert_eq!(calls[0], BackendCall::BindRenderTarget(None));
+    let sampled = calls
+      .iter()
+      .position(|call| call == &BackendCall::BindTexture { unit: 0, texture: Some(0) })
+      .expect("target texture bound");
+    assert!(calls.contains(&BackendCall::SetUniform {
+      program: 1,
+      name: "u_scene".to_owned(),
+      value: UniformValue::Sampler(0),
+    }));
+    assert!(calls.contains(&BackendCall::SetUniform {
+      program: 1,
+      name: "u_resolution".to_owned(),
+      value: Vec2::new(800.0, 600.0).into(),
+    }));
+    let drawn = calls
+      .iter()
+      .position(|call| {
+        call
+          == &BackendCall::Draw {
+            mode: DrawMode::TriangleStrip,
+            first: 0,
+            count: 4,
+          }
+      })
+      .expect("quad drawn");
+    assert!(sampled < drawn);
+    assert_eq!(calls.last(), Some(&BackendCall::SetRenderState(RenderState::default())));
+
+    let error = renderer.run_screen_pass(&pass, Some("scene")).unwrap_err();
+    assert!(error.to_string().contains("reads and writes render target `scene`"), "{error}");
+    assert!(renderer.run_screen_pass(&pass, Some("missing")).is_err());
+    assert!(renderer
+      .backend
+      .take_calls()
+      .iter()
+      .all(|call| !matches!(call, BackendCall::Draw { .. })));
+  }
+
+  #[test]
+  fn allocates_render_targets_again_after_resizing() {
+    let tree = triangle().compile_to_tree().unwrap();
+    let mut renderer = renderer();
+    renderer.set_render_target(RenderTargetSpec::new("half", 0.5, false));
+    renderer.paint_into(Some("half"), &tree).unwrap();
+    renderer.paint_into(Some("half"), &tree).unwrap();
+    let created = |calls: &[BackendCall]| -> Vec<(i32, i32)> {
+      calls
+        .iter()
+        .filter_map(|call| match call {
+          BackendCall::CreateRenderTarget { width, height, .. } => Some((*width, *height)),
+          _ => None,
+        })
+        .collect()
+    };
+    assert_eq!(created(&renderer.backend.take_calls()), [(400, 300)]);
+
+    renderer.backend.canvas_size = (1001, 3);
+    renderer.paint_into(Some("half"), &tree).unwrap();
+    let calls = renderer.backend.take_calls();
+    assert_eq!(calls[0], BackendCall::DeleteRenderTarget(0));
+    assert_eq!(created(&calls), [(501, 2)]);
+    assert_eq!(renderer.backend.live_render_targets(), 1);
+
+    renderer.dispose_render_target("half");
+    assert_eq!(renderer.backend.live_render_targets(), 0);
+    assert!(renderer.paint_into(Some("half"), &tree).is_err(), "unknown after disposing");
+  }
+
+  #[test]
+  fn draws_transparent_objects_last_from_far_to_near() {
+    let at = |z: f32| {
+      object(
+        DrawMode::Triangles,
+        VERTEX.to_owned(),
+        FRAGMENT.to_owned(),
+        vec![("a_position".to_owned(), 3)],
+        PackedAttrs::List(
+          [[0.0, 0.0, z], [10.0, 0.0, z], [0.0, 10.0, z]]
+            .into_iter()
+            .map(|p| PackedAttrs::Item(vec![VertexDataValue::Vec3(p)]))
+            .collect(),
+        ),
+        Rc::new(UniformMap::default),
+      )
+    };
+    let decal = RenderState {
+      cull_face: CullFace::Back,
+      polygon_offset: Some(PolygonOffset { factor: -1.0, units: -1.0 }),
+      ..RenderState::default()
+    };
+    let glass = RenderState::transparent(BlendMode::Alpha);
+    assert!(glass.is_transparent() && !glass.depth_write && !decal.is_transparent());
+    // vertex arrays are created in tree order, 0 to 3
+    let tree = group(vec![
+      at(100.0).with_render_state(glass),
+      at(0.0),
+      at(-100.0).with_render_state(glass),
+      at(0.0).with_render_state(decal),
+    ])
+    .compile_to_tree()
+    .unwrap();
+    let camera = Camera::looking_at(Vec3::new(0.0, 0.0, 600.0), Vec3::ZERO, Vec3::Y);
+    let viewports = [Viewport::new(ViewportRect::FULL, camera)];
+    let mut renderer = renderer();
+    renderer.paint_viewports(None, &tree, &viewports).unwrap();
+    renderer.backend.take_calls();
+
+    renderer.paint_viewports(None, &tree, &viewports).unwrap();
+    let states: Vec<BackendCall> = renderer
+      .backend
+      .take_calls()
+      .into_iter()
+      .filter(|call| matches!(call, BackendCall::SetRenderState(_) | BackendCall::BindVertexArray(Some(_))))
+      .collect();
+    assert_eq!(
+      states,
+      [
+        BackendCall::BindVertexArray(Some(1)),
+        BackendCall::SetRenderState(decal),
+        BackendCall::BindVertexArray(Some(3)),
+        BackendCall::SetRenderState(glass),
+        BackendCall::BindVertexArray(Some(2)),
+        // same state as the one before, not sent again
+        BackendCall::BindVertexArray(Some(0)),
+        BackendCall::SetRenderState(RenderState::default()),
+      ]
+    );
+  }
+
+  #[test]
+  fn passes_draw_modes_and_point_sizes() {
+    let shape = |draw_mode: DrawMode| {
+      object(
+        draw_mode,
+        VERTEX.to_owned(),
+        FRAGMENT.to_owned(),
+        vec![("a_position".to_owned(), 3)],
+        PackedAttrs::List(vec![PackedAttrs::Item(vec![VertexDataValue::Vec3([0.0; 3])]); 4]),
+        Rc::new(UniformMap::default),
+      )
+    };
+    let tree = group(vec![
+      shape(DrawMode::Points).with_point_size(6.0),
+      shape(DrawMode::LineLoop),
+      shape(DrawMode::TriangleFan),
+    ])
+    .compile_to_tree()
+    .unwrap();
+    let mut renderer = renderer();
+    renderer.paint(&tree).unwrap();
+    assert_eq!(
+      renderer.backend.draw_calls(),
+      [(DrawMode::Points, 0, 4), (DrawMode::LineLoop, 0, 4), (DrawMode::TriangleFan, 0, 4)]
+    );
+    let point_sizes: Vec<&UniformValue> = renderer
+      .backend
+      .calls
+      .iter()
+      .filter_map(|call| match call {
+        BackendCall::SetUniform { name, value, .. } if name == POINT_SIZE_UNIFORM => Some(value),
+        _ => None,
+      })
+      .collect();
+    assert_eq!(point_sizes, [&UniformValue::Float(6.0)], "only set for points");
+  }
+
+  #[test]
+  fn disposes_resources_of_trees() {
+    let tree = group(vec![triangle(), triangle()]).compile_to_tree().unwrap();
+    let mut renderer = renderer();
+    renderer.paint(&tree).unwrap();
+    assert_eq!(renderer.backend.live_programs(), 1, "same sources share a program");
+    assert_eq!((renderer.backend.live_buffers(), renderer.backend.live_vertex_arrays()), (2, 2));
+
+    renderer.dispose_tree(&tree);
+    let backend = &renderer.backend;
+    assert_eq!(
+      (backend.live_programs(), backend.live_buffers(), backend.live_vertex_arrays()),
+      (0, 0, 0)
+    );
+    assert_eq!(renderer.uploaded_count(), 0);
+  }
+
+  #[test]
+  fn keeps_textures_other_objects_use() {
+    let texture = Texture::procedural(2, 2, |x, y| [x as u8, y as u8, 0, 255]).unwrap();
+    let first = textured(&texture).compile_to_tree().unwrap();
+    let second = textured(&texture).compile_to_tree().unwrap();
+    let mut renderer = renderer();
+    renderer.paint(&first).unwrap();
+    renderer.paint(&second).unwrap();
+    assert_eq!(renderer.backend.live_textures(), 1);
+
+    renderer.dispose_tree(&first);
+    assert_eq!(renderer.backend.live_textures(), 1);
+    renderer.backend.take_calls();
+    renderer.paint(&second).unwrap();
+    assert!(renderer
+      .backend
+      .calls
+      .contains(&BackendCall::BindTexture { unit: 0, texture: Some(0) }));
+
+    // uploaded again when drawn after disposing
+    renderer.dispose_texture(texture.id);
+    renderer.paint(&second).unwrap();
+    assert!(renderer
+      .backend
+      .calls
+      .contains(&BackendCall::BindTexture { unit: 0, texture: Some(1) }));
+
+    renderer.dispose_tree(&second);
+    assert_eq!(renderer.backend.live_textures(), 0);
+  }
+
+  #[test]
+  fn uploads_textures_with_other_options_separately() {
+    let texture = Texture::procedural(1, 1, |_, _| [255; 4]).unwrap();
+    let repeated = texture.clone().with_options(TextureOptions {
+      wrap_s: TextureWrap::Repeat,
+      ..TextureOptions::default()
+    });
+    let tree = group(vec![textured(&texture), textured(&texture), textured(&repeated)])
+      .compile_to_tree()
+      .unwrap();
+    let mut renderer = renderer();
+    renderer.paint(&tree).unwrap();
+    assert_eq!(renderer.backend.live_textures(), 2);
+  }
+
+  #[test]
+  fn skips_empty_objects() {
+    let empty = object(
+      DrawMode::Triangles,
+      VERTEX.to_owned(),
+      FRAGMENT.to_owned(),
+      vec![("a_position".to_owned(), 3)],
+      PackedAttrs::List(Vec::new()),
+      Rc::new(UniformMap::default),
+    );
+    let tree = group(vec![empty, triangle()]).compile_to_tree().unwrap();
+    let mut renderer = renderer();
+    renderer.paint(&tree).unwrap();
+    assert_eq!(renderer.uploaded_count(), 1);
+    assert_eq!(renderer.backend.draw_calls(), [(DrawMode::Triangles, 0, 3)]);
+  }
+
+  #[test]
+  fn warns_about_attributes_programs_ignore() {
+    let tree = triangle().compile_to_tree().unwrap();
+    let mut backend = RecordingBackend::default();
+    backend.optimized_out.push("a_position".to_owned());
+    let mut renderer = Renderer::new(backend);
+    renderer.paint(&tree).unwrap();
+    assert!(!renderer
+      .backend
+      .calls
+      .iter()
+      .any(|call| matches!(call, BackendCall::AttribPointer { .. })));
+    let id = tree.objects()[0].id;
+    assert_eq!(
+      renderer.take_warnings(),
+      [ProgramWarning::InactiveAttribute {
+        object: id,
+        name: "a_position".to_owned(),
+      }]
+    );
+    renderer.paint(&tree).unwrap();
+    assert!(renderer.take_warnings().is_empty(), "reported once");
+  }
+
+  #[test]
+  fn relinks_objects_when_replacing_shaders() {
+    let tree = triangle().compile_to_tree().unwrap();
+    let mut renderer = renderer();
+    renderer.paint(&tree).unwrap();
+    let replacement = FRAGMENT.replace("vec4(1.0)", "vec4(0.5)");
+    assert_eq!(renderer.replace_shader(FRAGMENT, &replacement).unwrap(), 1);
+    assert!(renderer.backend.calls.contains(&BackendCall::DeleteProgram(0)));
+    assert_eq!(renderer.backend.live_programs(), 1);
+    assert_eq!(
+      renderer.backend.program_sources(1).map(|x| x.1.as_str()),
+      Some(replacement.as_str())
+    );
+    renderer.backend.take_calls();
+    renderer.paint(&tree).unwrap();
+    assert!(renderer.backend.calls.contains(&BackendCall::UseProgram(1)));
+  }
+
+  #[test]
+  fn culls_objects_out_of_view() {
+    let tree = group(vec![triangle(), triangle().without_culling()]).compile_to_tree().unwrap();
+    // facing away from the triangles
+    let camera = Camera::looking_at(Vec3::new(0.0, 0.0, 600.0), Vec3::new(0.0, 0.0, 1000.0), Vec3::Y);
+    let mut renderer = renderer();
+    renderer
+      .paint_viewports(None, &tree, &[Viewport::new(ViewportRect::FULL, camera)])
+      .unwrap();
+    assert_eq!(renderer.backend.draw_calls().len(), 1);
+    assert_eq!(renderer.draw_stats(), DrawStats { draws: 1, culled: 1 });
+
+    renderer.culling = false;
+    renderer.backend.take_calls();
+    renderer
+      .paint_viewports(None, &tree, &[Viewport::new(ViewportRect::FULL, camera)])
+      .unwrap();
+    assert_eq!(renderer.backend.draw_calls().len(), 2);
+  }
+}
@@ -0,0 +1,103 @@
+//! fixed-function states applied around each draw call, like blending and depth writes
+
+use web_sys::WebGl2RenderingContext;
+
+/// how fragment colors combine with colors already drawn
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+  /// replace colors, blending disabled
+  Opaque,
+  /// `src * a + dst * (1 - a)`
+  Alpha,
+  /// like `Alpha`, for colors already multiplied by alpha
+  Premultiplied,
+  /// `src * a + dst`, for glowing things
+  Additive,
+  /// `src * dst`
+  Multiply,
+}
+
+impl BlendMode {
+  /// GL constants of source and destination factors, `None` when blending is off
+  pub fn gl_factors(&self) -> Option<(u32, u32)> {
+    match self {
+      BlendMode::Opaque => None,
+      BlendMode::Alpha => Some((WebGl2RenderingContext::SRC_ALPHA, WebGl2RenderingContext::ONE_MINUS_SRC_ALPHA)),
+      BlendMode::Premultiplied => Some((WebGl2RenderingContext::ONE, WebGl2RenderingContext::ONE_MINUS_SRC_ALPHA)),
+      BlendMode::Additive => Some((WebGl2RenderingContext::SRC_ALPHA, WebGl2RenderingContext::ONE)),
+      BlendMode::Multiply => Some((WebGl2RenderingContext::DST_COLOR, WebGl2RenderingContext::ZERO)),
+    }
+  }
+}
+
+/// faces to skip, front faces wind counter-clockwise
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CullFace {
+  None,
+  Back,
+  Front,
+}
+
+/// pushes depth of polygons by `factor * slope + units`, to draw decals and outlines over surfaces
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PolygonOffset {
+  pub factor: f32,
+  pub units: f32,
+}
+
+/// render states of an object, defaults to opaque with depth test and depth writes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderState {
+  pub blend: BlendMode,
+  pub depth_test: bool,
+  pub depth_write: bool,
+  pub cull_face: CullFace,
+  pub polygon_offset: Option<PolygonOffset>,
+}
+
+impl Default for RenderState {
+  fn default() -> Self {
+    RenderState {
+      blend: BlendMode::Opaque,
+      depth_test: true,
+      depth_write: true,
+      cull_face: CullFace::None,
+      polygon_offset: None,
+    }
+  }
+}
+
+impl RenderState {
+  /// blended with depth test but without depth writes, so objects behind still show up
+  pub fn transparent(blend: BlendMode) -> Self {
+    RenderState {
+      blend,
+      depth_write: false,
+      ..Self::default()
+    }
+  }
+
+  pub fn alpha() -> Self {
+    Self::transparent(BlendMode::Alpha)
+  }
+
+  pub fn additive() -> Self {
+    Self::transparent(BlendMode::Additive)
+  }
+
+  /// transparent objects are drawn after opaque ones, from back to front
+  pub fn is_transparent(&self) -> bool {
+    self.blend != BlendMode::Opaque
+  }
+
+  pub fn with_cull_face(self, cull_face: CullFace) -> Self {
+    RenderState { cull_face, ..self }
+  }
+
+  pub fn with_polygon_offset(self, factor: f32, units: f32) -> Self {
+    RenderState {
+      polygon_offset: Some(PolygonOffset { factor, units }),
+      ..self
+    }
+  }
+}
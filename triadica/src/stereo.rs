@@ -0,0 +1,39 @@
+//! stereo rendering for cardboard viewers and anaglyph glasses, two cameras apart along `rightward`
+
+/// distance between eyes in world units, about a tenth of `LOOK_DISTANCE` like eyes to a screen
+pub const DEFAULT_EYE_SEPARATION: f32 = 60.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StereoOutput {
+  /// left eye in left half and right eye in right half, for cardboard style viewers
+  SideBySide,
+  /// left eye in red and right eye in green and blue, for red/cyan glasses
+  Anaglyph,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StereoOptions {
+  pub output: StereoOutput,
+  /// in world units
+  pub eye_separation: f32,
+}
+
+impl StereoOptions {
+  pub fn side_by_side() -> Self {
+    Self {
+      output: StereoOutput::SideBySide,
+      eye_separation: DEFAULT_EYE_SEPARATION,
+    }
+  }
+
+  pub fn anaglyph() -> Self {
+    Self {
+      output: StereoOutput::Anaglyph,
+      eye_separation: DEFAULT_EYE_SEPARATION,
+    }
+  }
+
+  pub fn with_eye_separation(self, eye_separation: f32) -> Self {
+    Self { eye_separation, ..self }
+  }
+}
@@ -0,0 +1,247 @@
+//! textures referenced by objects, uploaded once and bound to sampler uniforms at draw time
+
+use std::{
+  fmt::Debug,
+  rc::Rc,
+  sync::atomic::{AtomicU64, Ordering},
+};
+
+use wasm_bindgen::{closure::Closure, JsCast};
+use web_sys::{AddEventListenerOptions, HtmlImageElement, WebGl2RenderingContext};
+
+use crate::viewer;
+
+/// identifies a texture, objects sharing a texture share its GPU resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureId(u64);
+
+static TEXTURE_ID_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+impl TextureId {
+  pub fn next() -> Self {
+    TextureId(TEXTURE_ID_COUNTER.fetch_add(1, Ordering::Relaxed))
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureFilter {
+  Nearest,
+  Linear,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureWrap {
+  Repeat,
+  ClampToEdge,
+  MirroredRepeat,
+}
+
+impl From<TextureWrap> for i32 {
+  fn from(x: TextureWrap) -> Self {
+    (match x {
+      TextureWrap::Repeat => WebGl2RenderingContext::REPEAT,
+      TextureWrap::ClampToEdge => WebGl2RenderingContext::CLAMP_TO_EDGE,
+      TextureWrap::MirroredRepeat => WebGl2RenderingContext::MIRRORED_REPEAT,
+    }) as i32
+  }
+}
+
+/// sampling settings, defaults to linear filtering clamped to edges without mipmaps
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextureOptions {
+  pub min_filter: TextureFilter,
+  pub mag_filter: TextureFilter,
+  pub wrap_s: TextureWrap,
+  pub wrap_t: TextureWrap,
+  /// generate mipmaps after upload, `min_filter` then samples between levels
+  pub mipmaps: bool,
+}
+
+impl Default for TextureOptions {
+  fn default() -> Self {
+    TextureOptions {
+      min_filter: TextureFilter::Linear,
+      mag_filter: TextureFilter::Linear,
+      wrap_s: TextureWrap::ClampToEdge,
+      wrap_t: TextureWrap::ClampToEdge,
+      mipmaps: false,
+    }
+  }
+}
+
+impl TextureOptions {
+  /// GL constant for `TEXTURE_MIN_FILTER`, considering mipmaps
+  pub fn gl_min_filter(&self) -> i32 {
+    (match (self.min_filter, self.mipmaps) {
+      (TextureFilter::Nearest, false) => WebGl2RenderingContext::NEAREST,
+      (TextureFilter::Linear, false) => WebGl2RenderingContext::LINEAR,
+      (TextureFilter::Nearest, true) => WebGl2RenderingContext::NEAREST_MIPMAP_NEAREST,
+      (TextureFilter::Linear, true) => WebGl2RenderingContext::LINEAR_MIPMAP_LINEAR,
+    }) as i32
+  }
+
+  /// GL constant for `TEXTURE_MAG_FILTER`
+  pub fn gl_mag_filter(&self) -> i32 {
+    (match self.mag_filter {
+      TextureFilter::Nearest => WebGl2RenderingContext::NEAREST,
+      TextureFilter::Linear => WebGl2RenderingContext::LINEAR,
+    }) as i32
+  }
+}
+
+/// where pixels come from
+#[derive(Clone)]
+pub enum TextureSource {
+  /// 8-bit RGBA pixels, rows from top to bottom
+  Rgba { width: u32, height: u32, data: Vec<u8> },
+  /// image element, uploaded again once loaded when drawn earlier
+  Image(HtmlImageElement),
+}
+
+impl Debug for TextureSource {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      TextureSource::Rgba { width, height, .. } => f.write_fmt(format_args!("Rgba {width}x{height}")),
+      TextureSource::Image(_) => f.write_str("Image"),
+    }
+  }
+}
+
+impl TextureSource {
+  /// size of RGBA data, images are only known by the browser
+  pub fn size(&self) -> Option<(u32, u32)> {
+    match self {
+      TextureSource::Rgba { width, height, .. } => Some((*width, *height)),
+      TextureSource::Image(_) => None,
+    }
+  }
+
+  /// whether pixels are available, images are not until loaded
+  pub fn is_ready(&self) -> bool {
+    match self {
+      TextureSource::Rgba { .. } => true,
+      TextureSource::Image(image) => image.complete() && image.natural_width() > 0,
+    }
+  }
+}
+
+/// bytes of RGBA pixels, failing when it does not fit in `usize`, which is 32 bits on wasm
+fn rgba_len(width: u32, height: u32) -> Result<usize, String> {
+  (width as usize)
+    .checked_mul(height as usize)
+    .and_then(|pixels| pixels.checked_mul(4))
+    .ok_or_else(|| format!("{width}x{height} RGBA is too large"))
+}
+
+/// texture to be bound to a sampler uniform, cloning shares the source
+#[derive(Debug, Clone)]
+pub struct Texture {
+  pub id: TextureId,
+  pub source: Rc<TextureSource>,
+  pub options: TextureOptions,
+}
+
+impl Texture {
+  pub fn from_rgba(width: u32, height: u32, data: Vec<u8>) -> Result<Self, String> {
+    let expected = rgba_len(width, height)?;
+    if data.len() != expected {
+      return Err(format!("expected {expected} bytes for {width}x{height} RGBA, got {}", data.len()));
+    }
+    Ok(Self::from_source(TextureSource::Rgba { width, height, data }))
+  }
+
+  /// fill pixels by calling `f` with `(x, y)` of each pixel, like gradients or lookup tables
+  pub fn procedural<F>(width: u32, height: u32, f: F) -> Result<Self, String>
+  where
+    F: Fn(u32, u32) -> [u8; 4],
+  {
+    let mut data = Vec::with_capacity(rgba_len(width, height)?);
+    for y in 0..height {
+      for x in 0..width {
+        data.extend_from_slice(&f(x, y));
+      }
+    }
+    Ok(Self::from_source(TextureSource::Rgba { width, height, data }))
+  }
+
+  /// texture of an image element. when drawn before the image loads, it is blank until the load event,
+  /// which requests rendering so the renderer uploads it again
+  pub fn from_image(image: HtmlImageElement) -> Self {
+    if !image.complete() {
+      let on_load = Closure::once_into_js(viewer::mark_dirty);
+      let mut options = AddEventListenerOptions::new();
+      options.once(true);
+      let _ = image.add_event_listener_with_callback_and_add_event_listener_options("load", on_load.unchecked_ref(), &options);
+    }
+    Self::from_source(TextureSource::Image(image))
+  }
+
+  fn from_source(source: TextureSource) -> Self {
+    Self {
+      id: TextureId::next(),
+      source: Rc::new(source),
+      options: TextureOptions::default(),
+    }
+  }
+
+  /// same source with other options, as a new texture since options are applied when uploading
+  pub fn with_options(self, options: TextureOptions) -> Self {
+    Self {
+      id: TextureId::next(),
+      options,
+      ..self
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn checker() -> Texture {
+    Texture::procedural(2, 2, |x, y| if (x + y) % 2 == 0 { [255; 4] } else { [0, 0, 0, 255] }).unwrap()
+  }
+
+  #[test]
+  fn checks_sizes_of_rgba_data() {
+    assert!(Texture::from_rgba(2, 1, vec![0; 8]).is_ok());
+    let error = Texture::from_rgba(2, 2, vec![0; 8]).unwrap_err();
+    assert_eq!(error, "expected 16 bytes for 2x2 RGBA, got 8");
+    assert_eq!(Texture::from_rgba(0, 3, Vec::new()).map(|x| x.source.size()), Ok(Some((0, 3))));
+  }
+
+  #[test]
+  fn fills_procedural_pixels_by_rows() {
+    let texture = Texture::procedural(2, 2, |x, y| [x as u8, y as u8, 0, 255]).unwrap();
+    match texture.source.as_ref() {
+      TextureSource::Rgba { data, .. } => assert_eq!(data, &[0, 0, 0, 255, 1, 0, 0, 255, 0, 1, 0, 255, 1, 1, 0, 255]),
+      source => panic!("unexpected {source:?}"),
+    }
+    assert!(texture.source.is_ready());
+  }
+
+  #[test]
+  #[cfg(target_pointer_width = "32")]
+  fn rejects_sizes_beyond_usize() {
+    assert!(Texture::procedural(u32::MAX, 2, |_, _| [0; 4]).is_err());
+    assert!(Texture::from_rgba(65536, 16384, Vec::new()).is_err());
+  }
+
+  #[test]
+  fn shares_ids_between_clones_only() {
+    let texture = checker();
+    let clone = texture.clone();
+    assert_eq!(clone.id, texture.id);
+    assert!(Rc::ptr_eq(&clone.source, &texture.source));
+    assert_ne!(checker().id, texture.id);
+
+    let nearest = TextureOptions {
+      mag_filter: TextureFilter::Nearest,
+      ..TextureOptions::default()
+    };
+    let other = texture.clone().with_options(nearest);
+    assert_ne!(other.id, texture.id, "options are applied when uploading");
+    assert!(Rc::ptr_eq(&other.source, &texture.source));
+    assert_eq!(other.options.gl_mag_filter(), WebGl2RenderingContext::NEAREST as i32);
+  }
+}
@@ -0,0 +1,296 @@
+//! values for uniforms, covering scalars, vectors, matrices, arrays and samplers
+
+use std::collections::BTreeMap;
+
+use glam::{IVec2, IVec3, IVec4, Mat2, Mat3, Mat4, Vec2, Vec3, Vec4};
+
+use crate::primes::VertexDataValue;
+
+/// uniforms of an object by name, sorted so that binding order is stable
+pub type UniformMap = BTreeMap<String, UniformValue>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UniformValue {
+  Float(f32),
+  Vec2(Vec2),
+  Vec3(Vec3),
+  Vec4(Vec4),
+  Int(i32),
+  IVec2(IVec2),
+  IVec3(IVec3),
+  IVec4(IVec4),
+  Bool(bool),
+  Mat2(Mat2),
+  Mat3(Mat3),
+  Mat4(Mat4),
+  /// texture unit a sampler reads from
+  Sampler(u32),
+  /// `uniform T name[N]`, elements should share one kind and not be arrays
+  Array(Vec<UniformValue>),
+}
+
+/// type of a uniform value, arrays are described by their elements
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UniformKind {
+  Float,
+  Vec2,
+  Vec3,
+  Vec4,
+  Int,
+  IVec2,
+  IVec3,
+  IVec4,
+  Bool,
+  Mat2,
+  Mat3,
+  Mat4,
+  Sampler,
+}
+
+impl UniformKind {
+  /// type name used in GLSL declarations
+  pub fn glsl_type(&self) -> &'static str {
+    match self {
+      UniformKind::Float => "float",
+      UniformKind::Vec2 => "vec2",
+      UniformKind::Vec3 => "vec3",
+      UniformKind::Vec4 => "vec4",
+      UniformKind::Int => "int",
+      UniformKind::IVec2 => "ivec2",
+      UniformKind::IVec3 => "ivec3",
+      UniformKind::IVec4 => "ivec4",
+      UniformKind::Bool => "bool",
+      UniformKind::Mat2 => "mat2",
+      UniformKind::Mat3 => "mat3",
+      UniformKind::Mat4 => "mat4",
+      UniformKind::Sampler => "sampler2D",
+    }
+  }
+
+  /// integer kinds are bound with `uniform*i`
+  pub fn is_integer(&self) -> bool {
+    matches!(
+      self,
+      UniformKind::Int | UniformKind::IVec2 | UniformKind::IVec3 | UniformKind::IVec4 | UniformKind::Bool | UniformKind::Sampler
+    )
+  }
+}
+
+/// numbers of a uniform laid out as the `*v` binding functions expect, matrices in column-major order
+#[derive(Debug, Clone, PartialEq)]
+pub enum FlatUniform {
+  Floats(Vec<f32>),
+  Ints(Vec<i32>),
+}
+
+impl UniformValue {
+  /// kind of the value, or of elements for arrays
+  pub fn kind(&self) -> Result<UniformKind, String> {
+    Ok(match self {
+      UniformValue::Float(_) => UniformKind::Float,
+      UniformValue::Vec2(_) => UniformKind::Vec2,
+      UniformValue::Vec3(_) => UniformKind::Vec3,
+      UniformValue::Vec4(_) => UniformKind::Vec4,
+      UniformValue::Int(_) => UniformKind::Int,
+      UniformValue::IVec2(_) => UniformKind::IVec2,
+      UniformValue::IVec3(_) => UniformKind::IVec3,
+      UniformValue::IVec4(_) => UniformKind::IVec4,
+      UniformValue::Bool(_) => UniformKind::Bool,
+      UniformValue::Mat2(_) => UniformKind::Mat2,
+      UniformValue::Mat3(_) => UniformKind::Mat3,
+      UniformValue::Mat4(_) => UniformKind::Mat4,
+      UniformValue::Sampler(_) => UniformKind::Sampler,
+      UniformValue::Array(xs) => {
+        let first = xs.first().ok_or("uniform array should not be empty")?;
+        if matches!(first, UniformValue::Array(_)) {
+          return Err(String::from("nested uniform arrays are not supported"));
+        }
+        let kind = first.kind()?;
+        for x in xs {
+          if matches!(x, UniformValue::Array(_)) || x.kind()? != kind {
+            return Err(format!("uniform array mixes {:?} with {:?}", kind, x));
+          }
+        }
+        kind
+      }
+    })
+  }
+
+  /// number of elements, 1 for non-arrays
+  pub fn array_len(&self) -> usize {
+    match self {
+      UniformValue::Array(xs) => xs.len(),
+      _ => 1,
+    }
+  }
+
+  /// flatten numbers for binding, fails on mixed arrays
+  pub fn flatten(&self) -> Result<FlatUniform, String> {
+    let kind = self.kind()?;
+    let (mut floats, mut ints) = (Vec::new(), Vec::new());
+    self.push_numbers(&mut floats, &mut ints);
+    if kind.is_integer() {
+      Ok(FlatUniform::Ints(ints))
+    } else {
+      Ok(FlatUniform::Floats(floats))
+    }
+  }
+
+  /// numbers go to `floats` or `ints` by kind, elements of checked arrays all go to the same one
+  fn push_numbers(&self, floats: &mut Vec<f32>, ints: &mut Vec<i32>) {
+    match self {
+      UniformValue::Float(v) => floats.push(*v),
+      UniformValue::Vec2(v) => floats.extend_from_slice(&v.to_array()),
+      UniformValue::Vec3(v) => floats.extend_from_slice(&v.to_array()),
+      UniformValue::Vec4(v) => floats.extend_from_slice(&v.to_array()),
+      UniformValue::Mat2(m) => floats.extend_from_slice(&m.to_cols_array()),
+      UniformValue::Mat3(m) => floats.extend_from_slice(&m.to_cols_array()),
+      UniformValue::Mat4(m) => floats.extend_from_slice(&m.to_cols_array()),
+      UniformValue::Int(v) => ints.push(*v),
+      UniformValue::IVec2(v) => ints.extend_from_slice(&v.to_array()),
+      UniformValue::IVec3(v) => ints.extend_from_slice(&v.to_array()),
+      UniformValue::IVec4(v) => ints.extend_from_slice(&v.to_array()),
+      UniformValue::Bool(v) => ints.push(*v as i32),
+      UniformValue::Sampler(v) => ints.push(*v as i32),
+      UniformValue::Array(xs) => xs.iter().for_each(|x| x.push_numbers(floats, ints)),
+    }
+  }
+}
+
+impl From<f32> for UniformValue {
+  fn from(v: f32) -> Self {
+    UniformValue::Float(v)
+  }
+}
+
+impl From<Vec2> for UniformValue {
+  fn from(v: Vec2) -> Self {
+    UniformValue::Vec2(v)
+  }
+}
+
+impl From<Vec3> for UniformValue {
+  fn from(v: Vec3) -> Self {
+    UniformValue::Vec3(v)
+  }
+}
+
+impl From<Vec4> for UniformValue {
+  fn from(v: Vec4) -> Self {
+    UniformValue::Vec4(v)
+  }
+}
+
+impl From<i32> for UniformValue {
+  fn from(v: i32) -> Self {
+    UniformValue::Int(v)
+  }
+}
+
+impl From<IVec2> for UniformValue {
+  fn from(v: IVec2) -> Self {
+    UniformValue::IVec2(v)
+  }
+}
+
+impl From<IVec3> for UniformValue {
+  fn from(v: IVec3) -> Self {
+    UniformValue::IVec3(v)
+  }
+}
+
+impl From<IVec4> for UniformValue {
+  fn from(v: IVec4) -> Self {
+    UniformValue::IVec4(v)
+  }
+}
+
+impl From<bool> for UniformValue {
+  fn from(v: bool) -> Self {
+    UniformValue::Bool(v)
+  }
+}
+
+impl From<Mat2> for UniformValue {
+  fn from(v: Mat2) -> Self {
+    UniformValue::Mat2(v)
+  }
+}
+
+impl From<Mat3> for UniformValue {
+  fn from(v: Mat3) -> Self {
+    UniformValue::Mat3(v)
+  }
+}
+
+impl From<Mat4> for UniformValue {
+  fn from(v: Mat4) -> Self {
+    UniformValue::Mat4(v)
+  }
+}
+
+impl<T: Into<UniformValue>> From<Vec<T>> for UniformValue {
+  fn from(xs: Vec<T>) -> Self {
+    UniformValue::Array(xs.into_iter().map(Into::into).collect())
+  }
+}
+
+impl From<VertexDataValue> for UniformValue {
+  fn from(v: VertexDataValue) -> Self {
+    match v {
+      VertexDataValue::Float(x) => UniformValue::Float(x),
+      VertexDataValue::Vec2(x) => UniformValue::Vec2(x.into()),
+      VertexDataValue::Vec3(x) => UniformValue::Vec3(x.into()),
+      VertexDataValue::Vec4(x) => UniformValue::Vec4(x.into()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn rejects_empty_nested_and_mixed_arrays() {
+    assert!(UniformValue::Array(Vec::new()).kind().unwrap_err().contains("empty"));
+    let nested = UniformValue::Array(vec![UniformValue::Array(vec![1.0.into()])]);
+    assert!(nested.kind().unwrap_err().contains("nested"));
+    let nested_later = UniformValue::Array(vec![1.0.into(), UniformValue::Array(vec![1.0.into()])]);
+    assert!(nested_later.flatten().is_err());
+    let mixed = UniformValue::Array(vec![1.0.into(), 1.into()]);
+    assert!(mixed.flatten().unwrap_err().contains("mixes"));
+
+    let array: UniformValue = vec![Vec2::new(1.0, 2.0), Vec2::new(3.0, 4.0)].into();
+    assert_eq!((array.kind(), array.array_len()), (Ok(UniformKind::Vec2), 2));
+    assert_eq!(array.flatten(), Ok(FlatUniform::Floats(vec![1.0, 2.0, 3.0, 4.0])));
+  }
+
+  #[test]
+  fn flattens_matrices_by_columns() {
+    let m = Mat2::from_cols(Vec2::new(1.0, 2.0), Vec2::new(3.0, 4.0));
+    assert_eq!(UniformValue::Mat2(m).flatten(), Ok(FlatUniform::Floats(vec![1.0, 2.0, 3.0, 4.0])));
+    let translation = Mat4::from_translation(Vec3::new(5.0, 6.0, 7.0));
+    match UniformValue::Mat4(translation).flatten() {
+      Ok(FlatUniform::Floats(xs)) => assert_eq!(xs[12..], [5.0, 6.0, 7.0, 1.0]),
+      other => panic!("unexpected {other:?}"),
+    }
+    let m3 = UniformValue::Mat3(Mat3::from_cols(Vec3::X, Vec3::Y * 2.0, Vec3::Z * 3.0));
+    assert_eq!(
+      m3.flatten(),
+      Ok(FlatUniform::Floats(vec![1.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 3.0]))
+    );
+  }
+
+  #[test]
+  fn flattens_bools_and_samplers_to_ints() {
+    assert_eq!(UniformValue::Bool(true).flatten(), Ok(FlatUniform::Ints(vec![1])));
+    assert_eq!(UniformValue::Sampler(3).flatten(), Ok(FlatUniform::Ints(vec![3])));
+    let flags: UniformValue = vec![true, false, true].into();
+    assert_eq!(flags.flatten(), Ok(FlatUniform::Ints(vec![1, 0, 1])));
+    assert_eq!(
+      UniformValue::IVec3(IVec3::new(1, -2, 3)).flatten(),
+      Ok(FlatUniform::Ints(vec![1, -2, 3]))
+    );
+    assert!(UniformKind::Sampler.is_integer() && !UniformKind::Mat4.is_integer());
+  }
+}
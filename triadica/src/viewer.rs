@@ -1,4 +1,4 @@
-use glam::f32::Vec3;
+use glam::f32::{Vec2, Vec3};
 use std::sync::RwLock;
 
 lazy_static::lazy_static! {
@@ -52,6 +52,135 @@ pub fn get_directions() -> (Vec3, Vec3, Vec3) {
   (forward, upward, rightward)
 }
 
+/// distance where points are displayed at original size, passed as `lookDistance`
+pub const LOOK_DISTANCE: f32 = 600.0;
+/// how far the cone extends behind the viewer, passed as `coneBackScale`
+pub const CONE_BACK_SCALE: f32 = 0.5;
+/// scale from projected point to clip space, same as `gl_Position` in `demo.vert`
+pub const CLIP_SCALE: f32 = 0.002;
+
+/// snapshot of viewer states, carrying every value the cone-back perspective needs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+  pub position: Vec3,
+  pub forward: Vec3,
+  pub upward: Vec3,
+  pub rightward: Vec3,
+  pub look_distance: f32,
+  pub cone_back_scale: f32,
+  /// height divided by width
+  pub viewport_ratio: f32,
+}
+
+/// mirrors `PointResult` in `demo.vert`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PerspectivePoint {
+  pub point: Vec3,
+  pub r: f32,
+  pub s: f32,
+}
+
+/// points on screen at one position, `t` is distance along `forward` from where the ray starts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+  pub origin: Vec3,
+  /// not normalized, its part along `forward` is 1
+  pub direction: Vec3,
+}
+
+impl Ray {
+  pub fn at(&self, t: f32) -> Vec3 {
+    self.origin + self.direction * t
+  }
+}
+
+impl Camera {
+  /// a fixed camera at `position` facing `target`, tilted so `upward` points up as much as possible.
+  /// `upward` should not be parallel to the facing direction
+  pub fn looking_at(position: Vec3, target: Vec3, upward: Vec3) -> Self {
+    let forward = (target - position).normalize();
+    let rightward = upward.cross(forward).normalize();
+    Camera {
+      position,
+      forward,
+      upward: forward.cross(rightward),
+      rightward,
+      look_distance: LOOK_DISTANCE,
+      cone_back_scale: CONE_BACK_SCALE,
+      viewport_ratio: 1.0,
+    }
+  }
+
+  /// cameras of left and right eyes, moved apart along `rightward` and facing the same direction,
+  /// so the cone-back perspective of each eye stays the same as the mono view
+  pub fn stereo_eyes(&self, eye_separation: f32) -> [Camera; 2] {
+    let offset = self.rightward * (eye_separation * 0.5);
+    let eye = |position: Vec3| Camera { position, ..*self };
+    [eye(self.position - offset), eye(self.position + offset)]
+  }
+
+  /// same as `transform_perspective` in `demo.vert`
+  pub fn transform_perspective(&self, p: Vec3) -> PerspectivePoint {
+    let moved_point = p - self.position;
+    let s = self.cone_back_scale;
+    let r = moved_point.dot(self.forward) / self.look_distance;
+
+    if r < (s * -0.9) {
+      // make it disappear with depth test since it's probably behind the camera
+      return PerspectivePoint {
+        point: Vec3::new(0.0, 0.0, 10000.),
+        r,
+        s,
+      };
+    }
+
+    let screen_scale = (s + 1.0) / (r + s);
+    let y_next = moved_point.dot(self.upward) * screen_scale;
+    let x_next = -moved_point.dot(-self.rightward) * screen_scale;
+
+    PerspectivePoint {
+      point: Vec3::new(x_next, y_next / self.viewport_ratio, r),
+      r,
+      s,
+    }
+  }
+
+  /// inverse of `clip_position`, points projected to `clip` are on the ray.
+  /// the cone-back perspective is a pinhole at `coneBackScale * lookDistance` behind the camera, where the ray starts
+  pub fn ray_at(&self, clip: Vec2) -> Ray {
+    let s = self.cone_back_scale;
+    let k = 1.0 / (CLIP_SCALE * self.look_distance * (s + 1.0));
+    Ray {
+      origin: self.position - self.forward * (s * self.look_distance),
+      direction: self.forward + self.rightward * (clip.x * k) + self.upward * (clip.y * self.viewport_ratio * k),
+    }
+  }
+
+  /// `r` of `transform_perspective` for points at `t` on rays of `ray_at`
+  pub fn ray_depth(&self, t: f32) -> f32 {
+    t / self.look_distance - self.cone_back_scale
+  }
+
+  /// position in clip space, `w` is always 1
+  pub fn clip_position(&self, p: Vec3) -> Vec3 {
+    self.transform_perspective(p).point * CLIP_SCALE
+  }
+}
+
+/// read current viewer states into a camera
+pub fn get_camera() -> Camera {
+  let (forward, upward, rightward) = get_directions();
+  Camera {
+    position: get_camera_position(),
+    forward,
+    upward,
+    rightward,
+    look_distance: LOOK_DISTANCE,
+    cone_back_scale: CONE_BACK_SCALE,
+    viewport_ratio: *crate::WINDOW_RATIO.read().expect("to get window ratio"),
+  }
+}
+
 /// compare the point to viewer's position and angle
 pub fn to_viewer_axis(p: Vec3) -> Vec3 {
   let (forward, upward, rightward) = get_directions();
@@ -88,5 +217,30 @@ pub fn get_view_upward() -> Vec3 {
 }
 
 pub fn is_zero(x: f32) -> bool {
-  x.abs() < std::f32::EPSILON
+  x.abs() < f32::EPSILON
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn moves_eyes_apart_along_rightward() {
+    let camera = Camera::looking_at(Vec3::new(0.0, 0.0, 600.0), Vec3::ZERO, Vec3::Y);
+    let [left, right] = camera.stereo_eyes(60.0);
+    assert!((right.position - left.position - camera.rightward * 60.0).length() < 1e-4);
+    assert!(((left.position + right.position) * 0.5 - camera.position).length() < 1e-4);
+    for eye in [left, right] {
+      assert_eq!(
+        (eye.forward, eye.upward, eye.rightward),
+        (camera.forward, camera.upward, camera.rightward)
+      );
+      assert_eq!(eye.look_distance, camera.look_distance);
+    }
+    // a point straight ahead is seen on opposite sides of the screen by the two eyes
+    let ahead = Vec3::ZERO;
+    let seen = |eye: &Camera| eye.transform_perspective(ahead).point.x;
+    assert!(seen(&left) * seen(&right) < 0.0);
+    assert_eq!(camera.stereo_eyes(0.0), [camera, camera]);
+  }
 }
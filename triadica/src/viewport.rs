@@ -0,0 +1,86 @@
+//! rectangles of a render target drawn with their own cameras, for split screens, minimaps and overviews
+
+use crate::viewer::Camera;
+
+/// part of a render target, in fractions of its size from the bottom left corner
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ViewportRect {
+  pub x: f32,
+  pub y: f32,
+  pub width: f32,
+  pub height: f32,
+}
+
+impl ViewportRect {
+  /// covers the whole target
+  pub const FULL: ViewportRect = ViewportRect {
+    x: 0.0,
+    y: 0.0,
+    width: 1.0,
+    height: 1.0,
+  };
+
+  pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+    Self { x, y, width, height }
+  }
+
+  /// `x, y, width, height` in pixels of a target. edges are rounded,
+  /// so rectangles sharing an edge meet without gaps
+  pub fn pixels(&self, size: (i32, i32)) -> (i32, i32, i32, i32) {
+    let (w, h) = (size.0 as f32, size.1 as f32);
+    let left = (self.x * w).round() as i32;
+    let bottom = (self.y * h).round() as i32;
+    let right = ((self.x + self.width) * w).round() as i32;
+    let top = ((self.y + self.height) * h).round() as i32;
+    (left, bottom, right - left, top - bottom)
+  }
+
+  /// two halves side by side, left one first
+  pub fn split_horizontal() -> [ViewportRect; 2] {
+    [ViewportRect::new(0.0, 0.0, 0.5, 1.0), ViewportRect::new(0.5, 0.0, 0.5, 1.0)]
+  }
+}
+
+/// draws a tree into a rectangle with a camera, `viewport_ratio` of the camera is replaced by the one of the rectangle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+  pub rect: ViewportRect,
+  pub camera: Camera,
+}
+
+impl Viewport {
+  pub fn new(rect: ViewportRect, camera: Camera) -> Self {
+    Self { rect, camera }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn adjacent_rects_meet_without_gaps() {
+    for size in [(800, 600), (801, 599), (7, 3)] {
+      let thirds: Vec<_> = (0..3)
+        .map(|i| ViewportRect::new(i as f32 / 3.0, 0.0, 1.0 / 3.0, 1.0).pixels(size))
+        .collect();
+      assert_eq!(thirds[0].0, 0);
+      for pair in thirds.windows(2) {
+        assert_eq!(pair[0].0 + pair[0].2, pair[1].0, "{size:?}");
+      }
+      assert_eq!(thirds[2].0 + thirds[2].2, size.0);
+
+      let [left, right] = ViewportRect::split_horizontal().map(|rect| rect.pixels(size));
+      assert_eq!(left.0 + left.2, right.0);
+      assert_eq!(right.0 + right.2, size.0);
+      assert_eq!((left.3, right.3), (size.1, size.1));
+    }
+    assert_eq!(ViewportRect::FULL.pixels((800, 600)), (0, 0, 800, 600));
+  }
+
+  #[test]
+  fn rounds_tiny_rects_to_zero_size() {
+    assert_eq!(ViewportRect::new(0.5, 0.5, 0.0, 0.5).pixels((800, 600)), (400, 300, 0, 300));
+    assert_eq!(ViewportRect::new(0.5, 0.5, 0.5, 0.0001).pixels((800, 600)), (400, 300, 400, 0));
+  }
+}
@@ -0,0 +1,491 @@
+//! `RenderBackend` implemented with a `WebGl2RenderingContext`
+
+use std::{cell::RefCell, rc::Rc};
+
+use web_sys::{WebGl2RenderingContext, WebGlBuffer, WebGlFramebuffer, WebGlRenderbuffer, WebGlTexture, WebGlVertexArrayObject};
+
+use crate::backend::RenderBackend;
+use crate::error::{lost_or, TriadicaError};
+use crate::primes::{DrawMode, IndexType, Indices};
+use crate::program::{cached_link_program, release_program, retire_program, LinkedProgram, ProgramKey, ShaderProgramCaches};
+use crate::reflect::ProgramReflection;
+use crate::state::{CullFace, RenderState};
+use crate::texture::{Texture, TextureSource};
+use crate::uniform::{FlatUniform, UniformKind, UniformValue};
+
+pub struct WebGlBackend {
+  pub context: WebGl2RenderingContext,
+  pub caches: Rc<RefCell<ShaderProgramCaches>>,
+}
+
+impl WebGlBackend {
+  pub fn new(context: WebGl2RenderingContext, caches: Rc<RefCell<ShaderProgramCaches>>) -> Self {
+    Self { context, caches }
+  }
+}
+
+/// framebuffer drawing into a texture, with a depth renderbuffer when asked
+pub struct WebGlRenderTarget {
+  framebuffer: WebGlFramebuffer,
+  texture: WebGlTexture,
+  depth: Option<WebGlRenderbuffer>,
+  width: i32,
+  height: i32,
+}
+
+impl RenderBackend for WebGlBackend {
+  type Program = Rc<LinkedProgram>;
+  type Buffer = WebGlBuffer;
+  type VertexArray = WebGlVertexArrayObject;
+  type GpuTexture = WebGlTexture;
+  type RenderTarget = WebGlRenderTarget;
+
+  fn clear(&mut self) {
+    // context.color_mask(false, false, false, false);
+    self.context.clear_color(0.0, 0.0, 0.0, 1.0);
+    self
+      .context
+      .clear(WebGl2RenderingContext::COLOR_BUFFER_BIT | WebGl2RenderingContext::DEPTH_BUFFER_BIT);
+  }
+
+  fn drawing_buffer_size(&self) -> (i32, i32) {
+    (self.context.drawing_buffer_width(), self.context.drawing_buffer_height())
+  }
+
+  fn create_render_target(&mut self, width: i32, height: i32, depth: bool) -> Result<WebGlRenderTarget, TriadicaError> {
+    let mut made = PartialTarget::default();
+    allocate_render_target(&self.context, width, height, depth, &mut made).inspect_err(|_| made.delete(&self.context))
+  }
+
+  fn delete_render_target(&mut self, target: WebGlRenderTarget) {
+    self.context.delete_framebuffer(Some(&target.framebuffer));
+    self.context.delete_texture(Some(&target.texture));
+    if let Some(depth) = &target.depth {
+      self.context.delete_renderbuffer(Some(depth));
+    }
+  }
+
+  fn bind_render_target(&mut self, target: Option<&WebGlRenderTarget>) {
+    let context = &self.context;
+    context.disable(WebGl2RenderingContext::SCISSOR_TEST);
+    match target {
+      Some(t) => {
+        context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, Some(&t.framebuffer));
+        context.viewport(0, 0, t.width, t.height);
+      }
+      None => {
+        context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+        let (width, height) = self.drawing_buffer_size();
+        context.viewport(0, 0, width, height);
+      }
+    }
+  }
+
+  fn set_viewport(&mut self, x: i32, y: i32, width: i32, height: i32) {
+    let context = &self.context;
+    context.viewport(x, y, width, height);
+    // clearing ignores viewport, so it is limited with scissor
+    context.scissor(x, y, width, height);
+    context.enable(WebGl2RenderingContext::SCISSOR_TEST);
+  }
+
+  fn set_color_mask(&mut self, red: bool, green: bool, blue: bool, alpha: bool) {
+    self.context.color_mask(red, green, blue, alpha);
+  }
+
+  fn read_pixel(&mut self, x: i32, y: i32) -> Result<[u8; 4], TriadicaError> {
+    let mut pixel = [0; 4];
+    self
+      .context
+      .read_pixels_with_opt_u8_array(
+        x,
+        y,
+        1,
+        1,
+        WebGl2RenderingContext::RGBA,
+        WebGl2RenderingContext::UNSIGNED_BYTE,
+        Some(&mut pixel),
+      )
+      .map_err(|e| TriadicaError::Other(format!("failed to read pixel: {e:?}")))?;
+    Ok(pixel)
+  }
+
+  fn target_texture<'a>(&self, target: &'a WebGlRenderTarget) -> &'a WebGlTexture {
+    &target.texture
+  }
+
+  fn create_program(&mut self, key: ProgramKey, vert_shader: &str, frag_shader: &str) -> Result<Rc<LinkedProgram>, TriadicaError> {
+    cached_link_program(&self.context, key, vert_shader, frag_shader, self.caches.clone())
+  }
+
+  fn reflection<'a>(&'a self, program: &'a Rc<LinkedProgram>) -> &'a ProgramReflection {
+    &program.reflection
+  }
+
+  fn use_program(&mut self, program: &Rc<LinkedProgram>) {
+    self.context.use_program(Some(&program.program));
+  }
+
+  fn release_program(&mut self, program: Rc<LinkedProgram>) {
+    release_program(&self.context, program, self.caches.clone())
+  }
+
+  fn retire_program(&mut self, program: Rc<LinkedProgram>) {
+    retire_program(&self.context, program, self.caches.clone())
+  }
+
+  fn forget_resources(&mut self) {
+    self.caches.borrow_mut().forget();
+  }
+
+  fn set_render_state(&mut self, state: &RenderState) {
+    let context = &self.context;
+    let toggle = |cap: u32, on: bool| {
+      if on {
+        context.enable(cap);
+      } else {
+        context.disable(cap);
+      }
+    };
+
+    let factors = state.blend.gl_factors();
+    toggle(WebGl2RenderingContext::BLEND, factors.is_some());
+    if let Some((src, dst)) = factors {
+      context.blend_func(src, dst);
+    }
+
+    toggle(WebGl2RenderingContext::DEPTH_TEST, state.depth_test);
+    context.depth_mask(state.depth_write);
+
+    toggle(WebGl2RenderingContext::CULL_FACE, state.cull_face != CullFace::None);
+    match state.cull_face {
+      CullFace::None => {}
+      CullFace::Back => context.cull_face(WebGl2RenderingContext::BACK),
+      CullFace::Front => context.cull_face(WebGl2RenderingContext::FRONT),
+    }
+
+    toggle(WebGl2RenderingContext::POLYGON_OFFSET_FILL, state.polygon_offset.is_some());
+    if let Some(offset) = state.polygon_offset {
+      context.polygon_offset(offset.factor, offset.units);
+    }
+  }
+
+  fn create_buffer(&mut self, data: &[f32]) -> Result<WebGlBuffer, TriadicaError> {
+    let context = &self.context;
+    let buffer = context
+      .create_buffer()
+      .ok_or_else(|| lost_or(context, TriadicaError::BufferCreation(String::from("vertex buffer"))))?;
+    context.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&buffer));
+
+    // Note that `Float32Array::view` is somewhat dangerous (hence the
+    // `unsafe`!). This is creating a raw view into our module's
+    // `WebAssembly.Memory` buffer, but if we allocate more pages for ourself
+    // (aka do a memory allocation in Rust) it'll cause the buffer to change,
+    // causing the `Float32Array` to be invalid.
+    //
+    // As a result, after `Float32Array::view` we have to be very careful not to
+    // do any memory allocations before it's dropped.
+    unsafe {
+      let positions_array_buf_view = js_sys::Float32Array::view(data);
+
+      context.buffer_data_with_array_buffer_view(
+        WebGl2RenderingContext::ARRAY_BUFFER,
+        &positions_array_buf_view,
+        WebGl2RenderingContext::STATIC_DRAW,
+      );
+    }
+
+    Ok(buffer)
+  }
+
+  fn create_index_buffer(&mut self, indices: &Indices) -> Result<WebGlBuffer, TriadicaError> {
+    let context = &self.context;
+    let buffer = context
+      .create_buffer()
+      .ok_or_else(|| lost_or(context, TriadicaError::BufferCreation(String::from("index buffer"))))?;
+    context.bind_buffer(WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER, Some(&buffer));
+
+    // same as `create_buffer`, no allocations while the view is alive
+    unsafe {
+      match indices {
+        Indices::U16(xs) => context.buffer_data_with_array_buffer_view(
+          WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER,
+          &js_sys::Uint16Array::view(xs),
+          WebGl2RenderingContext::STATIC_DRAW,
+        ),
+        Indices::U32(xs) => context.buffer_data_with_array_buffer_view(
+          WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER,
+          &js_sys::Uint32Array::view(xs),
+          WebGl2RenderingContext::STATIC_DRAW,
+        ),
+      }
+    }
+
+    Ok(buffer)
+  }
+
+  fn bind_index_buffer(&mut self, buffer: &WebGlBuffer) {
+    self.context.bind_buffer(WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER, Some(buffer));
+  }
+
+  fn delete_buffer(&mut self, buffer: WebGlBuffer) {
+    self.context.delete_buffer(Some(&buffer));
+  }
+
+  fn create_vertex_array(&mut self) -> Result<WebGlVertexArrayObject, TriadicaError> {
+    let context = &self.context;
+    context
+      .create_vertex_array()
+      .ok_or_else(|| lost_or(context, TriadicaError::Other(String::from("Could not create vertex array object"))))
+  }
+
+  fn bind_vertex_array(&mut self, vertex_array: Option<&WebGlVertexArrayObject>) {
+    self.context.bind_vertex_array(vertex_array);
+  }
+
+  fn delete_vertex_array(&mut self, vertex_array: WebGlVertexArrayObject) {
+    self.context.delete_vertex_array(Some(&vertex_array));
+  }
+
+  fn attrib_pointer(
+    &mut self,
+    program: &Rc<LinkedProgram>,
+    attr_name: &str,
+    buffer: &WebGlBuffer,
+    unit_size: i32,
+    divisor: u32,
+  ) -> Result<(), TriadicaError> {
+    let context = &self.context;
+    let location = program.attrib_location(attr_name).ok_or_else(|| {
+      TriadicaError::AttributeMismatch(format!(
+        "attribute `{attr_name}` is not declared in vertex shader, or optimized out since unused"
+      ))
+    })?;
+    context.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(buffer));
+    context.vertex_attrib_pointer_with_i32(location, unit_size, WebGl2RenderingContext::FLOAT, false, 0, 0);
+    context.enable_vertex_attrib_array(location);
+    context.vertex_attrib_divisor(location, divisor);
+    Ok(())
+  }
+
+  fn create_texture(&mut self, texture: &Texture) -> Result<WebGlTexture, TriadicaError> {
+    let context = &self.context;
+    let target = WebGl2RenderingContext::TEXTURE_2D;
+    let handle = context
+      .create_texture()
+      .ok_or_else(|| lost_or(context, TriadicaError::Other(String::from("Failed to create texture"))))?;
+    context.bind_texture(target, Some(&handle));
+
+    let rgba = WebGl2RenderingContext::RGBA;
+    let uploaded = match &*texture.source {
+      TextureSource::Rgba { width, height, data } => context
+        .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+          target,
+          0,
+          rgba as i32,
+          *width as i32,
+          *height as i32,
+          0,
+          rgba,
+          WebGl2RenderingContext::UNSIGNED_BYTE,
+          Some(data),
+        ),
+      // a transparent pixel until the image loads, `Renderer` uploads it again then
+      TextureSource::Image(_) if !texture.source.is_ready() => context
+        .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+          target,
+          0,
+          rgba as i32,
+          1,
+          1,
+          0,
+          rgba,
+          WebGl2RenderingContext::UNSIGNED_BYTE,
+          Some(&[0, 0, 0, 0]),
+        ),
+      TextureSource::Image(image) => context.tex_image_2d_with_u32_and_u32_and_html_image_element(
+        target,
+        0,
+        rgba as i32,
+        rgba,
+        WebGl2RenderingContext::UNSIGNED_BYTE,
+        image,
+      ),
+    };
+    if let Err(e) = uploaded {
+      context.delete_texture(Some(&handle));
+      return Err(TriadicaError::Other(format!("failed to upload texture: {e:?}")));
+    }
+
+    let options = &texture.options;
+    context.tex_parameteri(target, WebGl2RenderingContext::TEXTURE_MIN_FILTER, options.gl_min_filter());
+    context.tex_parameteri(target, WebGl2RenderingContext::TEXTURE_MAG_FILTER, options.gl_mag_filter());
+    context.tex_parameteri(target, WebGl2RenderingContext::TEXTURE_WRAP_S, options.wrap_s.into());
+    context.tex_parameteri(target, WebGl2RenderingContext::TEXTURE_WRAP_T, options.wrap_t.into());
+    if options.mipmaps {
+      context.generate_mipmap(target);
+    }
+    context.bind_texture(target, None);
+
+    Ok(handle)
+  }
+
+  fn delete_texture(&mut self, texture: WebGlTexture) {
+    self.context.delete_texture(Some(&texture));
+  }
+
+  fn bind_texture(&mut self, unit: u32, texture: Option<&WebGlTexture>) {
+    self.context.active_texture(WebGl2RenderingContext::TEXTURE0 + unit);
+    self.context.bind_texture(WebGl2RenderingContext::TEXTURE_2D, texture);
+  }
+
+  fn set_uniform(&mut self, program: &Rc<LinkedProgram>, name: &str, value: &UniformValue) -> Result<(), TriadicaError> {
+    let context = &self.context;
+    // inactive uniforms are skipped by GL too
+    let location = program.uniform_location(name);
+    match value {
+      UniformValue::Float(v) => context.uniform1f(location, *v),
+      UniformValue::Vec2(v) => context.uniform2f(location, v.x, v.y),
+      UniformValue::Vec3(v) => context.uniform3f(location, v.x, v.y, v.z),
+      UniformValue::Vec4(v) => context.uniform4f(location, v.x, v.y, v.z, v.w),
+      UniformValue::Int(v) => context.uniform1i(location, *v),
+      UniformValue::IVec2(v) => context.uniform2i(location, v.x, v.y),
+      UniformValue::IVec3(v) => context.uniform3i(location, v.x, v.y, v.z),
+      UniformValue::IVec4(v) => context.uniform4i(location, v.x, v.y, v.z, v.w),
+      UniformValue::Bool(v) => context.uniform1i(location, *v as i32),
+      UniformValue::Sampler(v) => context.uniform1i(location, *v as i32),
+      UniformValue::Mat2(m) => context.uniform_matrix2fv_with_f32_array(location, false, &m.to_cols_array()),
+      UniformValue::Mat3(m) => context.uniform_matrix3fv_with_f32_array(location, false, &m.to_cols_array()),
+      UniformValue::Mat4(m) => context.uniform_matrix4fv_with_f32_array(location, false, &m.to_cols_array()),
+      UniformValue::Array(_) => match (value.kind()?, value.flatten()?) {
+        (UniformKind::Float, FlatUniform::Floats(xs)) => context.uniform1fv_with_f32_array(location, &xs),
+        (UniformKind::Vec2, FlatUniform::Floats(xs)) => context.uniform2fv_with_f32_array(location, &xs),
+        (UniformKind::Vec3, FlatUniform::Floats(xs)) => context.uniform3fv_with_f32_array(location, &xs),
+        (UniformKind::Vec4, FlatUniform::Floats(xs)) => context.uniform4fv_with_f32_array(location, &xs),
+        (UniformKind::Mat2, FlatUniform::Floats(xs)) => context.uniform_matrix2fv_with_f32_array(location, false, &xs),
+        (UniformKind::Mat3, FlatUniform::Floats(xs)) => context.uniform_matrix3fv_with_f32_array(location, false, &xs),
+        (UniformKind::Mat4, FlatUniform::Floats(xs)) => context.uniform_matrix4fv_with_f32_array(location, false, &xs),
+        (UniformKind::IVec2, FlatUniform::Ints(xs)) => context.uniform2iv_with_i32_array(location, &xs),
+        (UniformKind::IVec3, FlatUniform::Ints(xs)) => context.uniform3iv_with_i32_array(location, &xs),
+        (UniformKind::IVec4, FlatUniform::Ints(xs)) => context.uniform4iv_with_i32_array(location, &xs),
+        // int, bool and sampler arrays
+        (_, FlatUniform::Ints(xs)) => context.uniform1iv_with_i32_array(location, &xs),
+        (kind, _) => return Err(format!("unexpected data for {kind:?} array").into()),
+      },
+    }
+    Ok(())
+  }
+
+  fn draw(&mut self, mode: DrawMode, first: i32, count: i32) {
+    self.context.draw_arrays(mode.into(), first, count);
+  }
+
+  fn draw_elements(&mut self, mode: DrawMode, count: i32, index_type: IndexType) {
+    self.context.draw_elements_with_i32(mode.into(), count, index_type.into(), 0);
+  }
+
+  fn draw_instanced(&mut self, mode: DrawMode, first: i32, count: i32, instance_count: i32) {
+    self.context.draw_arrays_instanced(mode.into(), first, count, instance_count);
+  }
+
+  fn draw_elements_instanced(&mut self, mode: DrawMode, count: i32, index_type: IndexType, instance_count: i32) {
+    self
+      .context
+      .draw_elements_instanced_with_i32(mode.into(), count, index_type.into(), 0, instance_count);
+  }
+}
+
+/// objects of a render target created so far, deleted together when a later step fails
+#[derive(Default)]
+struct PartialTarget {
+  texture: Option<WebGlTexture>,
+  framebuffer: Option<WebGlFramebuffer>,
+  depth: Option<WebGlRenderbuffer>,
+}
+
+impl PartialTarget {
+  fn delete(self, context: &WebGl2RenderingContext) {
+    context.bind_texture(WebGl2RenderingContext::TEXTURE_2D, None);
+    context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+    context.bind_renderbuffer(WebGl2RenderingContext::RENDERBUFFER, None);
+    context.delete_texture(self.texture.as_ref());
+    context.delete_framebuffer(self.framebuffer.as_ref());
+    context.delete_renderbuffer(self.depth.as_ref());
+  }
+}
+
+/// create texture, framebuffer and depth renderbuffer, recording each in `made` as soon as it exists
+fn allocate_render_target(
+  context: &WebGl2RenderingContext,
+  width: i32,
+  height: i32,
+  depth: bool,
+  made: &mut PartialTarget,
+) -> Result<WebGlRenderTarget, TriadicaError> {
+  let target = WebGl2RenderingContext::TEXTURE_2D;
+  let texture = context
+    .create_texture()
+    .ok_or_else(|| lost_or(context, TriadicaError::Other(String::from("Failed to create texture"))))?;
+  made.texture = Some(texture.clone());
+  context.bind_texture(target, Some(&texture));
+  let rgba = WebGl2RenderingContext::RGBA;
+  context
+    .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+      target,
+      0,
+      rgba as i32,
+      width,
+      height,
+      0,
+      rgba,
+      WebGl2RenderingContext::UNSIGNED_BYTE,
+      None,
+    )
+    .map_err(|e| TriadicaError::Other(format!("failed to allocate target texture: {e:?}")))?;
+  let linear = WebGl2RenderingContext::LINEAR as i32;
+  let clamp = WebGl2RenderingContext::CLAMP_TO_EDGE as i32;
+  context.tex_parameteri(target, WebGl2RenderingContext::TEXTURE_MIN_FILTER, linear);
+  context.tex_parameteri(target, WebGl2RenderingContext::TEXTURE_MAG_FILTER, linear);
+  context.tex_parameteri(target, WebGl2RenderingContext::TEXTURE_WRAP_S, clamp);
+  context.tex_parameteri(target, WebGl2RenderingContext::TEXTURE_WRAP_T, clamp);
+  context.bind_texture(target, None);
+
+  let fb = WebGl2RenderingContext::FRAMEBUFFER;
+  let framebuffer = context
+    .create_framebuffer()
+    .ok_or_else(|| lost_or(context, TriadicaError::Other(String::from("Failed to create framebuffer"))))?;
+  made.framebuffer = Some(framebuffer.clone());
+  context.bind_framebuffer(fb, Some(&framebuffer));
+  context.framebuffer_texture_2d(fb, WebGl2RenderingContext::COLOR_ATTACHMENT0, target, Some(&texture), 0);
+
+  let depth = if depth {
+    let rb = WebGl2RenderingContext::RENDERBUFFER;
+    let renderbuffer = context
+      .create_renderbuffer()
+      .ok_or_else(|| lost_or(context, TriadicaError::Other(String::from("Failed to create renderbuffer"))))?;
+    made.depth = Some(renderbuffer.clone());
+    context.bind_renderbuffer(rb, Some(&renderbuffer));
+    context.renderbuffer_storage(rb, WebGl2RenderingContext::DEPTH_COMPONENT16, width, height);
+    context.framebuffer_renderbuffer(fb, WebGl2RenderingContext::DEPTH_ATTACHMENT, rb, Some(&renderbuffer));
+    context.bind_renderbuffer(rb, None);
+    Some(renderbuffer)
+  } else {
+    None
+  };
+
+  let status = context.check_framebuffer_status(fb);
+  context.bind_framebuffer(fb, None);
+  if status != WebGl2RenderingContext::FRAMEBUFFER_COMPLETE {
+    return Err(lost_or(
+      context,
+      TriadicaError::Other(format!("framebuffer incomplete, status {status:#x}")),
+    ));
+  }
+  Ok(WebGlRenderTarget {
+    framebuffer,
+    texture,
+    depth,
+    width,
+    height,
+  })
+}